@@ -3,11 +3,15 @@ use chrono::{DateTime, Local};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+use crate::tls::TlsOptions;
+
 #[derive(Debug)]
 pub enum UiEvent {
     AddMessage { content: String, is_system: bool },
     AddMessageWithId { id: Uuid, content: String, is_system: bool },
     UpdateMessage { id: Uuid, content: String },
+    /// The active room and its members, or `None` outside any room.
+    SetRoom { room: Option<String>, members: Vec<String> },
 }
 
 #[derive(Debug)]
@@ -41,21 +45,27 @@ impl Message {
 pub struct App {
     pub username: String,
     pub server_url: String,
+    pub token: Option<String>,
+    pub tls: TlsOptions,
     pub messages: Vec<Message>,
     pub input: String,
     pub current_room: Option<String>,
+    pub members: Vec<String>,
     pub should_quit: bool,
     pub tx: Option<mpsc::UnboundedSender<String>>,
 }
 
 impl App {
-    pub fn new(username: String, server_url: String) -> Result<Self> {
+    pub fn new(username: String, server_url: String, token: Option<String>, tls: TlsOptions) -> Result<Self> {
         Ok(Self {
             username,
             server_url,
+            token,
+            tls,
             messages: Vec::new(),
             input: String::new(),
             current_room: None,
+            members: Vec::new(),
             should_quit: false,
             tx: None,
         })
@@ -98,6 +108,10 @@ impl App {
             UiEvent::UpdateMessage { id, content } => {
                 self.update_message(id, content);
             }
+            UiEvent::SetRoom { room, members } => {
+                self.current_room = room;
+                self.members = members;
+            }
         }
     }
 
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
 #[derive(Parser)]
@@ -12,4 +14,16 @@ pub struct Args {
     /// Server URL
     #[arg(short, long, default_value = "ws://localhost:3000")]
     pub server: String,
+
+    /// Bearer token presented during the handshake
+    #[arg(short, long, env = "RWS_TOKEN")]
+    pub token: Option<String>,
+
+    /// Extra CA certificate (PEM) to trust for wss:// servers
+    #[arg(long, env = "RWS_CA_CERT")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Accept any server certificate (development only)
+    #[arg(long)]
+    pub insecure: bool,
 }
@@ -1,25 +1,44 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use anyhow::Result;
 use futures_util::{SinkExt, StreamExt};
-use rws_common::{ChatScope, EventMessage, UserInfo};
+use rws_common::{ChatScope, EventMessage, RoomInfo, RoomRole, RoomVisibility, UserInfo, PROTOCOL_VERSION};
 use tokio::sync::{mpsc, Mutex};
-use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
+use tokio_tungstenite::{
+    connect_async_tls_with_config,
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message as WsMessage},
+};
 use url::Url;
 use uuid::Uuid;
 
-use crate::app::UiEvent;
+use crate::{app::UiEvent, tls::TlsOptions};
 
 pub async fn connect_and_handle(
     username: String,
     server_url: String,
+    token: Option<String>,
+    tls: TlsOptions,
     ui_tx: mpsc::UnboundedSender<UiEvent>,
     mut ws_rx: mpsc::UnboundedReceiver<String>,
 ) -> Result<()> {
-    let (ws_stream, _) = connect_async(Url::parse(&server_url)?).await?;
+    let mut request = Url::parse(&server_url)?.into_client_request()?;
+    if let Some(token) = token {
+        request
+            .headers_mut()
+            .insert("Authorization", HeaderValue::from_str(&format!("Bearer {}", token))?);
+    }
+    let (ws_stream, _) = connect_async_tls_with_config(request, None, false, Some(tls.connector()?)).await?;
     let (mut write, mut read) = ws_stream.split();
 
+    // Servers that predate Hello reject it as unknown and still accept Join
+    let hello = EventMessage::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        client_name: format!("rws-client/{}", env!("CARGO_PKG_VERSION")),
+        capabilities: Vec::new(),
+    };
+    write.send(WsMessage::Text(serde_json::to_string(&hello)?)).await?;
+
     let join = EventMessage::Join {
         username: username.clone(),
     };
@@ -30,69 +49,61 @@ pub async fn connect_and_handle(
         is_system: true,
     })?;
 
-    let self_id = Arc::new(Mutex::new(None));
+    let session = Arc::new(Mutex::new(Session::default()));
     let pending_msgs = Arc::new(Mutex::new(HashMap::<Uuid, String>::new()));
+    // The room plain messages are sent to: the one joined most recently
+    let active_room = Arc::new(Mutex::new(None::<RoomInfo>));
+    let roster = Arc::new(Mutex::new(Roster::default()));
 
     // Reader
     {
-        let self_id = Arc::clone(&self_id);
+        let session = Arc::clone(&session);
         let ui_tx = ui_tx.clone();
         let pending_msgs = Arc::clone(&pending_msgs);
+        let active_room = Arc::clone(&active_room);
+        let roster = Arc::clone(&roster);
 
         tokio::spawn(async move {
             while let Some(msg) = read.next().await {
-                if let Ok(WsMessage::Text(text)) = msg {
-                    if let Ok(event) = serde_json::from_str::<EventMessage>(&text) {
-                        match &event {
-                            EventMessage::AssignedId { user_id } => {
-                                let mut id = self_id.lock().await;
-                                *id = Some(*user_id);
-                            }
+                if let Ok(WsMessage::Text(text)) = msg
+                    && let Ok(event) = serde_json::from_str::<EventMessage>(&text)
+                {
+                    let self_id = {
+                        let mut session = session.lock().await;
+                        session.update(&event);
+                        session.joined_id()
+                    };
 
-                            EventMessage::Chat { id, sender, content, scope } => {
-                                let my_id = self_id.lock().await;
-                                if let Some(my_id) = *my_id {
-                                    // Check if this is our own message coming back from server
-                                    if sender.id == my_id {
-                                        // This is our message being echoed back - treat as delivery confirmation
-                                        let mut pending = pending_msgs.lock().await;
-                                        if let Some(original_content) = pending.remove(id) {
-                                            let delivered_msg = match scope {
-                                                ChatScope::Global => format!("[GLOBAL]💬 You: {} ✅", original_content),
-                                                ChatScope::Room { room } => format!("[{}]🏠 You: {} ✅", room.name, original_content),
-                                            };
-                                            let _ = ui_tx.send(UiEvent::UpdateMessage {
-                                                id: *id,
-                                                content: delivered_msg,
-                                            });
-                                        }
-                                    } else {
-                                        // This is someone else's message
-                                        let formatted = format_message(event, &my_id);
-                                        if !formatted.is_empty() {
-                                            let _ = ui_tx.send(UiEvent::AddMessage {
-                                                content: formatted,
-                                                is_system: false,
-                                            });
-                                        }
-                                    }
-                                }
-                            }
+                    if let Some(my_id) = self_id {
+                        let mut active = active_room.lock().await;
+                        track_active_room(&event, my_id, &mut active);
+                        let mut roster = roster.lock().await;
+                        if roster.update(&event, active.as_ref()) {
+                            let _ = ui_tx.send(roster.ui_event());
+                        }
+                    }
 
-                            EventMessage::AckDelivered { id } => {
-                                let mut pending = pending_msgs.lock().await;
-                                if let Some(content) = pending.remove(id) {
-                                    let delivered_content = format!("[GLOBAL]💬 You: {} ", content);
-                                    let _ = ui_tx.send(UiEvent::UpdateMessage {
-                                        id: *id,
-                                        content: delivered_content,
-                                    });
-                                }
-                            }
+                    match &event {
+                        EventMessage::AssignedId { .. } | EventMessage::Welcome { .. } => {}
 
-                            _ => {
-                                let my_id = self_id.lock().await;
-                                if let Some(my_id) = *my_id {
+                        EventMessage::Chat { id, sender, scope, .. } => {
+                            if let Some(my_id) = self_id {
+                                // Check if this is our own message coming back from server
+                                if sender.as_ref().is_some_and(|s| s.id == my_id) {
+                                    // This is our message being echoed back - treat as delivery confirmation
+                                    let mut pending = pending_msgs.lock().await;
+                                    if let Some(original_content) = pending.remove(id) {
+                                        let delivered_msg = match scope {
+                                            ChatScope::Global => format!("[GLOBAL]💬 You: {} ✅", original_content),
+                                            ChatScope::Room { room } => format!("[{}]🏠 You: {} ✅", room.name, original_content),
+                                        };
+                                        let _ = ui_tx.send(UiEvent::UpdateMessage {
+                                            id: *id,
+                                            content: delivered_msg,
+                                        });
+                                    }
+                                } else {
+                                    // This is someone else's message
                                     let formatted = format_message(event, &my_id);
                                     if !formatted.is_empty() {
                                         let _ = ui_tx.send(UiEvent::AddMessage {
@@ -103,6 +114,37 @@ pub async fn connect_and_handle(
                                 }
                             }
                         }
+
+                        EventMessage::AckDelivered { id } => {
+                            let mut pending = pending_msgs.lock().await;
+                            if let Some(content) = pending.remove(id) {
+                                let delivered_content = format!("[GLOBAL]💬 You: {} ", content);
+                                let _ = ui_tx.send(UiEvent::UpdateMessage {
+                                    id: *id,
+                                    content: delivered_content,
+                                });
+                            }
+                        }
+
+                        EventMessage::Error { .. } if self_id.is_none() => {
+                            // e.g. the requested username was rejected
+                            let _ = ui_tx.send(UiEvent::AddMessage {
+                                content: format!("{} (use /nick <name> to retry)", format_message(event, &Uuid::nil())),
+                                is_system: true,
+                            });
+                        }
+
+                        _ => {
+                            if let Some(my_id) = self_id {
+                                let formatted = format_message(event, &my_id);
+                                if !formatted.is_empty() {
+                                    let _ = ui_tx.send(UiEvent::AddMessage {
+                                        content: formatted,
+                                        is_system: false,
+                                    });
+                                }
+                            }
+                        }
                     }
                 }
             }
@@ -115,52 +157,80 @@ pub async fn connect_and_handle(
             continue;
         }
 
-        let my_id = *self_id.lock().await;
-        if my_id.is_none() {
-            continue;
-        }
-        let my_id = my_id.unwrap();
+        let (joined, protocol_version) = {
+            let session = session.lock().await;
+            (session.joined, session.protocol_version)
+        };
 
-        let message = if input.starts_with("/create ") {
-            let room_name = input["/create ".len()..].to_string();
+        let message = if let Some(new_name) = input.strip_prefix("/nick ") {
+            nick_message(new_name.trim().to_string(), joined)
+        } else if !joined {
+            continue;
+        } else if let Some(room_name) = input.strip_prefix("/create ") {
+            let room_name = room_name.to_string();
             EventMessage::CreateRoom {
-                creator: UserInfo {
-                    id: my_id,
-                    username: username.clone(),
-                },
+                creator: None,
                 room_name,
+                topic: None,
+                visibility: RoomVisibility::Public,
+            }
+        } else if input.starts_with("/members") {
+            match active_room.lock().await.clone() {
+                Some(room) => EventMessage::GetRoomMembers { room },
+                None => {
+                    ui_tx.send(UiEvent::AddMessage {
+                        content: "❌ You are not in a room".to_string(),
+                        is_system: true,
+                    })?;
+                    continue;
+                }
+            }
+        } else if let Some(filter) = input.strip_prefix("/rooms") {
+            let filter = filter.trim();
+            EventMessage::ListRooms {
+                filter: (!filter.is_empty()).then(|| filter.to_string()),
+                cursor: None,
             }
-        } else if input.starts_with("/join ") {
-            let room_id_str = input["/join ".len()..].trim();
-            match Uuid::parse_str(room_id_str) {
+        } else if let Some(room) = input.strip_prefix("/join ") {
+            let room = room.trim();
+            match Uuid::parse_str(room) {
                 Ok(room_id) => EventMessage::JoinRoom {
-                    user: UserInfo {
-                        id: my_id,
-                        username: username.clone(),
-                    },
-                    room: rws_common::RoomInfo {
+                    user: None,
+                    room: RoomInfo {
                         id: room_id,
                         name: "".to_string(),
                     },
                 },
-                Err(_) => {
+                Err(_) => EventMessage::JoinRoomByName {
+                    user: None,
+                    room_name: room.to_string(),
+                },
+            }
+        } else if let Some(command) = moderation_command(&input, &*roster.lock().await) {
+            let command = command.and_then(|message| match protocol_version {
+                Some(version) if version >= MODERATION_VERSION => Ok(message),
+                _ => Err("This server does not support moderation".to_string()),
+            });
+            match command {
+                Ok(message) => message,
+                Err(problem) => {
                     ui_tx.send(UiEvent::AddMessage {
-                        content: "❌ Invalid room ID format. Use: /join <room-uuid>".to_string(),
+                        content: format!("❌ {}", problem),
                         is_system: true,
                     })?;
                     continue;
                 }
             }
         } else if input.starts_with("/leave") {
-            EventMessage::LeaveRoom {
-                user: UserInfo {
-                    id: my_id,
-                    username: username.clone(),
-                },
-                room: rws_common::RoomInfo {
-                    id: Uuid::nil(),
-                    name: "".to_string(),
-                },
+            match active_room.lock().await.clone() {
+                Some(room) => EventMessage::LeaveRoom { user: None, room },
+                None => {
+                    ui_tx.send(UiEvent::AddMessage {
+                        content: "❌ You are not in a room".to_string(),
+                        is_system: true,
+                    })?;
+                    continue;
+                }
             }
         } else {
             let msg_id = Uuid::new_v4();
@@ -171,20 +241,22 @@ pub async fn connect_and_handle(
                 pending.insert(msg_id, input.clone());
             }
 
+            let room = active_room.lock().await.clone();
+            let content = match &room {
+                Some(room) => format!("[{}]🏠 You: {} ⏳", room.name, input),
+                None => format!("[GLOBAL]💬 You: {} ⏳", input),
+            };
             ui_tx.send(UiEvent::AddMessageWithId {
                 id: msg_id,
-                content: format!("[GLOBAL]💬 You: {} ⏳", input),
+                content,
                 is_system: false,
             })?;
 
             EventMessage::Chat {
                 id: msg_id,
-                sender: UserInfo {
-                    id: my_id,
-                    username: username.clone(),
-                },
+                sender: None,
                 content: input,
-                scope: ChatScope::Global,
+                scope: room.map_or(ChatScope::Global, |room| ChatScope::Room { room }),
             }
         };
 
@@ -195,57 +267,330 @@ pub async fn connect_and_handle(
     Ok(())
 }
 
+/// First protocol version with room moderators, kicks and bans.
+const MODERATION_VERSION: u32 = 3;
+
+/// What the server has told us about this connection so far.
+#[derive(Debug, Default)]
+struct Session {
+    /// From `Welcome`, or `AssignedId` on servers that predate it.
+    user_id: Option<Uuid>,
+    /// Version agreed in `Welcome`; `None` for servers that predate it.
+    protocol_version: Option<u32>,
+    /// Set once a `Join` is accepted. `Welcome` comes first, so having an id
+    /// does not mean the requested username was.
+    joined: bool,
+}
+
+impl Session {
+    fn update(&mut self, event: &EventMessage) {
+        match event {
+            EventMessage::Welcome { user_id, protocol_version, .. } => {
+                self.user_id = Some(*user_id);
+                self.protocol_version = Some(*protocol_version);
+            }
+            EventMessage::AssignedId { user_id } => {
+                self.user_id = Some(*user_id);
+                self.joined = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Our id once joined; nothing is shown before then.
+    fn joined_id(&self) -> Option<Uuid> {
+        self.user_id.filter(|_| self.joined)
+    }
+}
+
+/// `/nick` renames once joined, and until then retries the `Join`.
+fn nick_message(username: String, joined: bool) -> EventMessage {
+    if joined {
+        EventMessage::ChangeUsername { user: None, username }
+    } else {
+        EventMessage::Join { username }
+    }
+}
+
 fn format_message(event: EventMessage, self_id: &Uuid) -> String {
     use EventMessage::*;
     match event {
         Chat {
             id: _,
-            sender: UserInfo { id, username },
+            sender,
             content,
             scope,
-        } => match scope {
-            ChatScope::Global => {
-                if &id == self_id {
-                    // Already handled via ack
-                    "".into()
-                } else {
-                    format!("[GLOBAL]💬 {}: {}", username, content)
+        } => {
+            let (id, username) = identity(sender);
+            match scope {
+                ChatScope::Global => {
+                    if &id == self_id {
+                        // Already handled via ack
+                        "".into()
+                    } else {
+                        format!("[GLOBAL]💬 {}: {}", username, content)
+                    }
                 }
-            }
-            ChatScope::Room { room } => {
-                if &id == self_id {
-                    format!("[{}]🏠 You: {}", room.name, content)
-                } else {
-                    format!("[{}]🏠 {}: {}", room.name, username, content)
+                ChatScope::Room { room } => {
+                    if &id == self_id {
+                        format!("[{}]🏠 You: {}", room.name, content)
+                    } else {
+                        format!("[{}]🏠 {}: {}", room.name, username, content)
+                    }
                 }
             }
-        },
+        }
         Join { username } => format!("👋 {} joined", username),
         CreateRoom {
-            creator: UserInfo { username, .. },
+            creator,
             room_name,
-        } => format!("🏠 Room '{}' created by '{}'", room_name, username),
-        JoinRoom {
-            user: UserInfo { id, username },
-            room,
-        } => {
+            ..
+        } => format!("🏠 Room '{}' created by '{}'", room_name, identity(creator).1),
+        RoomCreated { room } => format!("🏠 Room '{}' created, id {}", room.name, room.id),
+        RoomList { rooms, next_cursor } => {
+            let mut lines = vec![format!("🏠 {} room(s):", rooms.len())];
+            for room in rooms {
+                let topic = room.topic.map(|t| format!(" - {}", t)).unwrap_or_default();
+                lines.push(format!("  #{} {} ({} members){}", room.slug, room.name, room.member_count, topic));
+            }
+            if next_cursor.is_some() {
+                lines.push("  … more rooms match; narrow the filter".to_string());
+            }
+            lines.join("\n")
+        }
+        JoinRoom { user, room } => {
+            let (id, username) = identity(user);
             if &id == self_id {
                 format!("✅ You joined room {}", room.name)
             } else {
                 format!("👥 {} joined room {}", username, room.name)
             }
         }
-        LeaveRoom {
-            user: UserInfo { id, username },
-            room,
-        } => {
+        LeaveRoom { user, room } => {
+            let (id, username) = identity(user);
             if &id == self_id {
                 format!("🚪 You left room {}", room.name)
             } else {
                 format!("👋 {} left room {}", username, room.name)
             }
         }
+        ChangeUsername { user, username } => {
+            let (id, old_username) = identity(user);
+            if &id == self_id {
+                format!("✏️ You are now known as {}", username)
+            } else {
+                format!("✏️ {} is now known as {}", old_username, username)
+            }
+        }
+        RoomRoleChanged { room, user, role } => {
+            let role = match role {
+                RoomRole::Owner => "the owner",
+                RoomRole::Moderator => "a moderator",
+                RoomRole::Member => "a member",
+            };
+            if &user.id == self_id {
+                format!("🎖️ You are now {} of room {}", role, room.name)
+            } else {
+                format!("🎖️ {} is now {} of room {}", user.username, role, room.name)
+            }
+        }
+        MemberKicked { room, user, by, reason } => {
+            let reason = reason.map(|r| format!(": {}", r)).unwrap_or_default();
+            if &user.id == self_id {
+                format!("👢 You were kicked from room {} by {}{}", room.name, by.username, reason)
+            } else {
+                format!("👢 {} was kicked from room {} by {}{}", user.username, room.name, by.username, reason)
+            }
+        }
+        MemberBanned { room, user, by, reason, duration_secs } => {
+            let duration = duration_secs.map(|secs| format!(" for {}m", secs.div_ceil(60))).unwrap_or_default();
+            let reason = reason.map(|r| format!(": {}", r)).unwrap_or_default();
+            if &user.id == self_id {
+                format!("⛔ You were banned from room {}{} by {}{}", room.name, duration, by.username, reason)
+            } else {
+                format!("⛔ {} was banned from room {}{} by {}{}", user.username, room.name, duration, by.username, reason)
+            }
+        }
+        Disconnected { user } => format!("🔌 {} disconnected", user.username),
+        ServerShutdown { reconnect_after_secs } => match reconnect_after_secs {
+            Some(secs) => format!("🛑 Server is shutting down, reconnect in {}s", secs),
+            None => "🛑 Server is shutting down".to_string(),
+        },
+        Announcement { message } => format!("📢 {}", message),
+        RoomDeleted { room } => format!("🗑️ Room {} was deleted", room.name),
         Error { error } => format!("❌ Error: {:?}", error),
         _ => "".into(),
     }
 }
+
+/// Follow our own room memberships so plain messages go to the room joined
+/// most recently, falling back to global chat after leaving it.
+fn track_active_room(event: &EventMessage, self_id: Uuid, active: &mut Option<RoomInfo>) {
+    let is_active = |room: &RoomInfo| active.as_ref().is_some_and(|a| a.id == room.id);
+    match event {
+        EventMessage::JoinRoom { user: Some(user), room } if user.id == self_id => *active = Some(room.clone()),
+        EventMessage::LeaveRoom { user: Some(user), room } if user.id == self_id && is_active(room) => *active = None,
+        EventMessage::RoomDeleted { room } if is_active(room) => *active = None,
+        _ => {}
+    }
+}
+
+/// Members of the active room, kept current from the `RoomMembers` snapshot
+/// and the join, leave and rename events that follow it.
+#[derive(Default)]
+struct Roster {
+    room: Option<RoomInfo>,
+    members: Vec<UserInfo>,
+    roles: BTreeMap<Uuid, RoomRole>,
+}
+
+impl Roster {
+    /// Apply `event`, returning whether the sidebar needs redrawing.
+    fn update(&mut self, event: &EventMessage, active: Option<&RoomInfo>) -> bool {
+        let showing = |room: &RoomInfo| self.room.as_ref().is_some_and(|r| r.id == room.id);
+        match event {
+            EventMessage::RoomMembers { room, members, roles } if active.is_some_and(|a| a.id == room.id) => {
+                self.room = Some(room.clone());
+                self.members = members.clone();
+                self.roles = roles.clone();
+            }
+            // The room on show was left or another became active
+            _ if self.room.is_some() && active.is_none_or(|a| !showing(a)) => *self = Roster::default(),
+            EventMessage::JoinRoom { user: Some(user), room } if showing(room) => {
+                if !self.members.iter().any(|m| m.id == user.id) {
+                    self.members.push(user.clone());
+                }
+            }
+            EventMessage::LeaveRoom { user: Some(user), room } if showing(room) => {
+                self.members.retain(|m| m.id != user.id);
+                self.roles.remove(&user.id);
+            }
+            EventMessage::RoomRoleChanged { room, user, role } if showing(room) => {
+                self.roles.insert(user.id, *role);
+            }
+            EventMessage::ChangeUsername { user: Some(user), username } => {
+                let Some(member) = self.members.iter_mut().find(|m| m.id == user.id) else { return false };
+                member.username = username.clone();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn ui_event(&self) -> UiEvent {
+        let members = self
+            .members
+            .iter()
+            .map(|m| match self.roles.get(&m.id) {
+                Some(RoomRole::Owner) => format!("★ {}", m.username),
+                Some(RoomRole::Moderator) => format!("@ {}", m.username),
+                _ => format!("  {}", m.username),
+            })
+            .collect();
+        UiEvent::SetRoom {
+            room: self.room.as_ref().map(|r| r.name.clone()),
+            members,
+        }
+    }
+}
+
+/// Parse `/op`, `/deop`, `/owner`, `/kick` and `/ban`, which act on the room
+/// shown in `roster` and name users as they appear there. `None` for other
+/// input.
+///
+/// `/kick <user> [reason]`, `/ban <user> [minutes] [reason]`
+fn moderation_command(input: &str, roster: &Roster) -> Option<Result<EventMessage, String>> {
+    let (command, args) = input.split_once(' ').unwrap_or((input, ""));
+    if !matches!(command, "/op" | "/deop" | "/owner" | "/kick" | "/ban") {
+        return None;
+    }
+
+    let Some(room) = roster.room.clone() else {
+        return Some(Err("You are not in a room".to_string()));
+    };
+    let (name, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+    if name.is_empty() {
+        return Some(Err(format!("Usage: {} <user>", command)));
+    }
+    // Bans may name someone outside the room by id
+    let target = roster
+        .members
+        .iter()
+        .find(|m| m.username.eq_ignore_ascii_case(name))
+        .map(|m| m.id)
+        .or_else(|| Uuid::parse_str(name).ok().filter(|_| command == "/ban"));
+    let Some(user_id) = target else {
+        return Some(Err(format!("No member named {} in room {}", name, room.name)));
+    };
+    let reason = |text: &str| (!text.trim().is_empty()).then(|| text.trim().to_string());
+
+    let message = match command {
+        "/op" => EventMessage::SetRoomRole { room, user_id, role: RoomRole::Moderator },
+        "/deop" => EventMessage::SetRoomRole { room, user_id, role: RoomRole::Member },
+        "/owner" => EventMessage::SetRoomRole { room, user_id, role: RoomRole::Owner },
+        "/kick" => EventMessage::KickMember { room, user_id, reason: reason(rest) },
+        _ => {
+            let (minutes, text) = rest.trim().split_once(' ').unwrap_or((rest.trim(), ""));
+            match minutes.parse::<u64>() {
+                Ok(minutes) => EventMessage::BanMember {
+                    room,
+                    user_id,
+                    reason: reason(text),
+                    duration_secs: Some(minutes.saturating_mul(60)),
+                },
+                Err(_) => EventMessage::BanMember { room, user_id, reason: reason(rest), duration_secs: None },
+            }
+        }
+    };
+    Some(Ok(message))
+}
+
+/// Split a server-filled identity into id and display name.
+fn identity(user: Option<UserInfo>) -> (Uuid, String) {
+    user.map_or_else(|| (Uuid::nil(), "Unknown".to_string()), |u| (u.id, u.username))
+}
+
+#[cfg(test)]
+mod tests {
+    use rws_common::ProtocolLimits;
+
+    use super::*;
+
+    fn welcome(user_id: Uuid) -> EventMessage {
+        EventMessage::Welcome {
+            user_id,
+            protocol_version: PROTOCOL_VERSION,
+            server_version: "test".to_string(),
+            capabilities: Vec::new(),
+            limits: ProtocolLimits { max_message_size: 1024, max_room_name_len: 64, username_min_len: 2, username_max_len: 32 },
+        }
+    }
+
+    #[test]
+    fn nick_retries_a_refused_join_until_one_is_accepted() {
+        let user_id = Uuid::new_v4();
+        let mut session = Session::default();
+
+        session.update(&welcome(user_id));
+        assert_eq!(session.protocol_version, Some(PROTOCOL_VERSION));
+        // The server refused the Join that followed Hello
+        session.update(&EventMessage::Error {
+            error: rws_common::ErrorCode::UsernameTaken { message: "taken".to_string() },
+        });
+        assert_eq!(session.joined_id(), None);
+        assert!(matches!(nick_message("bob".into(), session.joined), EventMessage::Join { username } if username == "bob"));
+
+        session.update(&EventMessage::AssignedId { user_id });
+        assert_eq!(session.joined_id(), Some(user_id));
+        assert!(matches!(nick_message("rob".into(), session.joined), EventMessage::ChangeUsername { username, .. } if username == "rob"));
+    }
+
+    #[test]
+    fn servers_without_welcome_are_joined_by_assigned_id() {
+        let user_id = Uuid::new_v4();
+        let mut session = Session::default();
+        session.update(&EventMessage::AssignedId { user_id });
+        assert_eq!(session.joined_id(), Some(user_id));
+        assert_eq!(session.protocol_version, None);
+    }
+}
@@ -1,6 +1,7 @@
 mod app;
 mod cli;
 mod client;
+mod tls;
 mod ui;
 
 use anyhow::Result;
@@ -10,7 +11,11 @@ use clap::Parser;
 async fn main() -> Result<()> {
     let args = cli::Args::parse();
     
-    let mut app = app::App::new(args.username, args.server)?;
+    let tls = tls::TlsOptions {
+        ca_cert: args.ca_cert,
+        insecure: args.insecure,
+    };
+    let mut app = app::App::new(args.username, args.server, args.token, tls)?;
     ui::run(&mut app).await?;
     
     Ok(())
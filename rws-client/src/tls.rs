@@ -0,0 +1,77 @@
+use std::{fs, io::BufReader, path::PathBuf, sync::Arc, time::SystemTime};
+
+use anyhow::{anyhow, Context, Result};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName,
+};
+use tokio_tungstenite::Connector;
+
+/// How to verify the server when connecting to a `wss://` URL.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// Extra PEM CA certificate to trust, e.g. for a self-signed server.
+    pub ca_cert: Option<PathBuf>,
+    /// Skip certificate verification entirely. Development only.
+    pub insecure: bool,
+}
+
+impl TlsOptions {
+    pub fn connector(&self) -> Result<Connector> {
+        let builder = ClientConfig::builder().with_safe_defaults();
+
+        let config = if self.insecure {
+            let mut config = builder
+                .with_root_certificates(RootCertStore::empty())
+                .with_no_client_auth();
+            config
+                .dangerous()
+                .set_certificate_verifier(Arc::new(NoVerification));
+            config
+        } else {
+            builder
+                .with_root_certificates(self.root_store()?)
+                .with_no_client_auth()
+        };
+
+        Ok(Connector::Rustls(Arc::new(config)))
+    }
+
+    fn root_store(&self) -> Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+        }));
+
+        if let Some(path) = &self.ca_cert {
+            let pem = fs::read(path).with_context(|| format!("failed to read CA certificate {}", path.display()))?;
+            let certs = rustls_pemfile::certs(&mut BufReader::new(pem.as_slice()))
+                .context("invalid CA certificate PEM")?;
+            if certs.is_empty() {
+                return Err(anyhow!("no certificates found in {}", path.display()));
+            }
+            for cert in certs {
+                roots.add(&Certificate(cert))?;
+            }
+        }
+
+        Ok(roots)
+    }
+}
+
+/// Accepts any server certificate.
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
@@ -30,8 +30,10 @@ pub async fn run(app: &mut App) -> Result<()> {
 
     let username = app.username.clone();
     let server_url = app.server_url.clone();
+    let token = app.token.clone();
+    let tls = app.tls.clone();
     tokio::spawn(async move {
-        if let Err(e) = client::connect_and_handle(username, server_url, ui_tx, ws_rx).await {
+        if let Err(e) = client::connect_and_handle(username, server_url, token, tls, ui_tx, ws_rx).await {
             eprintln!("WebSocket error: {}", e);
         }
     });
@@ -50,13 +52,11 @@ pub async fn run(app: &mut App) -> Result<()> {
                         KeyCode::Char('q') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
                             app.quit();
                         }
-                        KeyCode::Enter => {
-                            if !app.input.is_empty() {
-                                if let Some(tx) = &app.tx {
-                                    let _ = tx.send(app.input.clone());
-                                }
-                                app.clear_input();
+                        KeyCode::Enter if !app.input.is_empty() => {
+                            if let Some(tx) = &app.tx {
+                                let _ = tx.send(app.input.clone());
                             }
+                            app.clear_input();
                         }
                         KeyCode::Char(c) => {
                             app.input.push(c);
@@ -125,7 +125,22 @@ fn draw_ui(f: &mut ratatui::Frame, app: &App) {
     let messages_list = List::new(messages)
         .block(Block::default().borders(Borders::ALL).title("Messages"))
         .style(Style::default().fg(Color::White));
-    f.render_widget(messages_list, chunks[1]);
+
+    if app.members.is_empty() {
+        f.render_widget(messages_list, chunks[1]);
+    } else {
+        let body = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(24)])
+            .split(chunks[1]);
+        f.render_widget(messages_list, body[0]);
+
+        let members: Vec<ListItem> = app.members.iter().map(|m| ListItem::new(m.as_str())).collect();
+        let sidebar = List::new(members)
+            .block(Block::default().borders(Borders::ALL).title(format!("Members ({})", app.members.len())))
+            .style(Style::default().fg(Color::Cyan));
+        f.render_widget(sidebar, body[1]);
+    }
 
     let input = Paragraph::new(app.input.as_str())
         .block(
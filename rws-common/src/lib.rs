@@ -1,8 +1,83 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+/// Version of the event protocol defined in this crate. Bump it for changes
+/// that existing clients would misread.
+///
+/// - 1: `Hello`/`Welcome` handshake.
+/// - 2: a user may be in several rooms; `Chat` and `LeaveRoom` name their
+///   room. Older clients' `Global` chats and nil-id leaves go to the room they
+///   joined most recently.
+/// - 3: room moderators, kicks and bans; `RoomRole` gains `Moderator`.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Oldest protocol version still understood.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// `Sec-WebSocket-Protocol` value naming protocol `version`, e.g. `rws.v1`.
+pub fn subprotocol(version: u32) -> String {
+    format!("rws.v{}", version)
+}
+
+/// The protocol version named by a `Sec-WebSocket-Protocol` value, if it is
+/// one of ours.
+pub fn parse_subprotocol(value: &str) -> Option<u32> {
+    value.trim().strip_prefix("rws.v")?.parse().ok()
+}
+
+/// The normalized form of a room name, unique per server and accepted when
+/// joining by name: lowercase, without a leading `#`, with runs of
+/// whitespace, `-` and `_` turned into a single `-` and other punctuation
+/// dropped. `None` if nothing is left.
+pub fn room_slug(name: &str) -> Option<String> {
+    let mut slug = String::new();
+    let mut separator = false;
+    for c in name.trim().trim_start_matches('#').chars() {
+        if c.is_alphanumeric() {
+            if separator && !slug.is_empty() {
+                slug.push('-');
+            }
+            separator = false;
+            slug.extend(c.to_lowercase());
+        } else if c.is_whitespace() || c == '-' || c == '_' {
+            separator = true;
+        }
+    }
+    (!slug.is_empty()).then_some(slug)
+}
+
+/// Optional features a server may advertise in `Welcome`.
+pub mod capability {
+    pub const ROOMS: &str = "rooms";
+    pub const RENAME: &str = "rename";
+    pub const ROOM_LIST: &str = "room_list";
+    pub const MODERATION: &str = "moderation";
+}
+
+/// Identity fields (`sender`, `reader`, `creator`, `user`) are filled in by the
+/// server from the connection; clients may omit them.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "event", content = "data")]
 pub enum EventMessage {
+    /// Opens a versioned session. Answered by `Welcome`, or by an
+    /// `UnsupportedVersion` error and a close. Clients that skip it and start
+    /// with `Join` are served as before.
+    Hello {
+        protocol_version: u32,
+        client_name: String,
+        #[serde(default)]
+        capabilities: Vec<String>,
+    },
+    /// Reply to `Hello`, or sent unprompted after a protocol was negotiated
+    /// through `Sec-WebSocket-Protocol`.
+    Welcome {
+        user_id: uuid::Uuid,
+        protocol_version: u32,
+        server_version: String,
+        capabilities: Vec<String>,
+        limits: ProtocolLimits,
+    },
     Join {
         username: String,
     },
@@ -11,7 +86,8 @@ pub enum EventMessage {
     },
     Chat {
         id: uuid::Uuid,
-        sender: UserInfo,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sender: Option<UserInfo>,
         content: String,
         scope: ChatScope,
     },
@@ -20,18 +96,140 @@ pub enum EventMessage {
     },
     AckRead {
         id: uuid::Uuid,
-        reader: UserInfo,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reader: Option<UserInfo>,
     },
     CreateRoom {
-        creator: UserInfo,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        creator: Option<UserInfo>,
         room_name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        topic: Option<String>,
+        #[serde(default)]
+        visibility: RoomVisibility,
     },
-    JoinRoom {
+    /// Reply to `CreateRoom` carrying the new room's id.
+    RoomCreated {
+        room: RoomInfo,
+    },
+    /// Ask for one page of the rooms the sender can see. `filter` matches
+    /// names and topics ignoring case; `cursor` comes from a previous
+    /// `RoomList`.
+    ListRooms {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filter: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cursor: Option<String>,
+    },
+    /// Ask for the roster of a room the sender belongs to.
+    GetRoomMembers {
+        room: RoomInfo,
+    },
+    /// Everyone in a room and their roles, sent on joining and on request.
+    /// Later changes arrive as `JoinRoom` and `LeaveRoom`.
+    RoomMembers {
+        room: RoomInfo,
+        members: Vec<UserInfo>,
+        roles: BTreeMap<uuid::Uuid, RoomRole>,
+    },
+    /// Give a member of a room another role. Only the owner may; making
+    /// someone `Owner` hands the room over and leaves the previous owner a
+    /// moderator.
+    SetRoomRole {
+        room: RoomInfo,
+        user_id: uuid::Uuid,
+        role: RoomRole,
+    },
+    /// Sent to a room when a member's role changes, including when ownership
+    /// passes on because the owner left.
+    RoomRoleChanged {
+        room: RoomInfo,
+        user: UserInfo,
+        role: RoomRole,
+    },
+    /// Remove a member from a room. Owners may kick anyone else, moderators
+    /// only plain members.
+    KickMember {
+        room: RoomInfo,
+        user_id: uuid::Uuid,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// Sent to a room, the kicked member included, just before the kick's
+    /// `LeaveRoom`.
+    MemberKicked {
+        room: RoomInfo,
         user: UserInfo,
+        by: UserInfo,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// Remove someone from a room and refuse their joins for `duration_secs`,
+    /// or for as long as the room exists. Allowed whenever `KickMember` would
+    /// be, and for users who are not in the room.
+    BanMember {
+        room: RoomInfo,
+        user_id: uuid::Uuid,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_secs: Option<u64>,
+    },
+    /// Sent to a room and to the banned user, before any `LeaveRoom`.
+    MemberBanned {
+        room: RoomInfo,
+        user: UserInfo,
+        by: UserInfo,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration_secs: Option<u64>,
+    },
+    /// Rooms ordered by name. `next_cursor` is set when more remain.
+    RoomList {
+        rooms: Vec<RoomListing>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        next_cursor: Option<String>,
+    },
+    JoinRoom {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user: Option<UserInfo>,
         room: RoomInfo,
     },
+    /// Join a room by name or slug (`#backend`, `Backend`). Private rooms
+    /// can only be joined by id.
+    JoinRoomByName {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user: Option<UserInfo>,
+        room_name: String,
+    },
     LeaveRoom {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user: Option<UserInfo>,
+        room: RoomInfo,
+    },
+    ChangeUsername {
+        /// The user's identity before the change.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user: Option<UserInfo>,
+        username: String,
+    },
+    /// Presence update sent to everyone when a joined user's connection ends.
+    Disconnected {
         user: UserInfo,
+    },
+    /// Sent to every client just before the server closes their connections.
+    ServerShutdown {
+        /// Suggested delay before reconnecting, if the server expects to return.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reconnect_after_secs: Option<u64>,
+    },
+    /// System message from the operator, sent to every client.
+    Announcement {
+        message: String,
+    },
+    /// Sent to a room's members when an operator deletes it.
+    RoomDeleted {
         room: RoomInfo,
     },
     Error {
@@ -40,6 +238,48 @@ pub enum EventMessage {
     Ping,
 }
 
+/// Who can find a room in `RoomList`. Private rooms are listed only to their
+/// members; anyone with the id can still join.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomVisibility {
+    #[default]
+    Public,
+    Private,
+}
+
+/// A member's standing in a room, highest first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomRole {
+    Owner,
+    /// May kick and ban plain members.
+    Moderator,
+    Member,
+}
+
+/// A room as shown in `RoomList`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoomListing {
+    pub id: uuid::Uuid,
+    pub name: String,
+    /// See [`room_slug`].
+    pub slug: String,
+    pub member_count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    pub visibility: RoomVisibility,
+}
+
+/// Server limits a client should respect to avoid errors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProtocolLimits {
+    pub max_message_size: usize,
+    pub max_room_name_len: usize,
+    pub username_min_len: usize,
+    pub username_max_len: usize,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserInfo {
     pub id: uuid::Uuid,
@@ -49,6 +289,8 @@ pub struct UserInfo {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RoomInfo {
     pub id: uuid::Uuid,
+    /// Filled in by the server; clients naming a room by id may leave it empty.
+    #[serde(default)]
     pub name: String,
 }
 
@@ -60,6 +302,46 @@ pub enum ErrorCode {
     AlreadyInRoom { message: String },
     InvalidRoomId { message: String },
     PermissionDenied { message: String },
+    IdentityMismatch { message: String },
+    UsernameTaken { message: String },
+    InvalidUsername { message: String },
+    LimitExceeded { message: String },
+    /// A text frame that is not a valid event. `line` and `column` locate the
+    /// parse error within the frame, both starting at 1.
+    MalformedMessage { message: String, line: usize, column: usize },
+    /// A binary frame, or an event only the server sends.
+    UnsupportedEvent { message: String },
+    /// `Hello` asked for a protocol version outside `min_version..=max_version`.
+    UnsupportedVersion { message: String, min_version: u32, max_version: u32 },
+    /// A room name with nothing left after normalization; see [`room_slug`].
+    InvalidRoomName { message: String },
+    /// A `ListRooms` cursor that this server did not issue.
+    InvalidCursor { message: String },
+    /// A moderation event naming someone who is not in the room.
+    UserNotFound { message: String },
+}
+
+impl ErrorCode {
+    /// Stable snake_case name of the variant, for metrics and logs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ErrorCode::RoomNotFound { .. } => "room_not_found",
+            ErrorCode::RoomAlreadyExists { .. } => "room_already_exists",
+            ErrorCode::AlreadyInRoom { .. } => "already_in_room",
+            ErrorCode::InvalidRoomId { .. } => "invalid_room_id",
+            ErrorCode::PermissionDenied { .. } => "permission_denied",
+            ErrorCode::IdentityMismatch { .. } => "identity_mismatch",
+            ErrorCode::UsernameTaken { .. } => "username_taken",
+            ErrorCode::InvalidUsername { .. } => "invalid_username",
+            ErrorCode::LimitExceeded { .. } => "limit_exceeded",
+            ErrorCode::MalformedMessage { .. } => "malformed_message",
+            ErrorCode::UnsupportedEvent { .. } => "unsupported_event",
+            ErrorCode::UnsupportedVersion { .. } => "unsupported_version",
+            ErrorCode::InvalidCursor { .. } => "invalid_cursor",
+            ErrorCode::InvalidRoomName { .. } => "invalid_room_name",
+            ErrorCode::UserNotFound { .. } => "user_not_found",
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
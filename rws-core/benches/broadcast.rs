@@ -0,0 +1,187 @@
+//! Broadcast throughput with many connected clients, comparing the original
+//! design with the current one:
+//!
+//! - `mutex`: one `Mutex<HashMap>` of clients, each holding its
+//!   `Mutex<SplitSink>`. A broadcast keeps the registry locked while it awaits
+//!   the write to every client in turn.
+//! - `registry`: the sharded `ClientRegistry`. A broadcast pushes into each
+//!   client's `Outbound` queue and a writer task per client drains it.
+//!
+//! Each design runs with every client fast, and again with one client in a
+//! thousand whose writes take a millisecond, as a congested peer would.
+//! Throughput counts frames delivered to the fast clients.
+//!
+//! Run with `cargo bench -p rws-core --bench broadcast`.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures_util::{sink, Sink, SinkExt};
+use rws_common::{ChatScope, EventMessage, UserInfo};
+use rws_core::{util::broadcast::send, Client, ClientRegistry, Clients, Metrics, Outbound, SlowConsumerPolicy};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use uuid::Uuid;
+
+const SENDERS: usize = 8;
+const BROADCASTS_PER_SENDER: usize = 25;
+/// How long each write to a slow client takes.
+const SLOW_WRITE: Duration = Duration::from_millis(1);
+/// Per-client queue size in the current design, as in `OutboundConfig`.
+const QUEUE_CAPACITY: usize = 256;
+
+type BoxSink = Pin<Box<dyn Sink<WsMessage, Error = Infallible> + Send>>;
+
+/// Stands in for a client's socket. Frames written to fast clients are
+/// counted in `delivered`.
+fn client_sink(slow: bool, delivered: &Arc<AtomicU64>) -> BoxSink {
+    let delivered = Arc::clone(delivered);
+    Box::pin(sink::unfold((), move |(), _message: WsMessage| {
+        let delivered = Arc::clone(&delivered);
+        async move {
+            if slow {
+                tokio::time::sleep(SLOW_WRITE).await;
+            } else {
+                delivered.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok::<_, Infallible>(())
+        }
+    }))
+}
+
+fn is_slow(index: usize, with_slow: bool) -> bool {
+    with_slow && index % 1000 == 999
+}
+
+fn chat(sender: Uuid) -> EventMessage {
+    EventMessage::Chat {
+        id: Uuid::new_v4(),
+        sender: Some(UserInfo {
+            id: sender,
+            username: "user".to_string(),
+        }),
+        content: "hello".to_string(),
+        scope: ChatScope::Global,
+    }
+}
+
+/// The original broadcast: the registry stays locked across every awaited write.
+async fn bench_mutex(clients: usize, with_slow: bool, delivered: &Arc<AtomicU64>) -> Duration {
+    let registry: Arc<Mutex<HashMap<Uuid, Arc<Mutex<BoxSink>>>>> = Arc::new(Mutex::new(
+        (0..clients)
+            .map(|i| (Uuid::new_v4(), Arc::new(Mutex::new(client_sink(is_slow(i, with_slow), delivered)))))
+            .collect(),
+    ));
+
+    let start = Instant::now();
+    let tasks: Vec<_> = (0..SENDERS)
+        .map(|_| {
+            let registry = Arc::clone(&registry);
+            tokio::spawn(async move {
+                for _ in 0..BROADCASTS_PER_SENDER {
+                    let sender = *registry.lock().await.keys().next().unwrap();
+                    let payload = serde_json::to_string(&chat(sender)).unwrap();
+                    let clients = registry.lock().await;
+                    for tx in clients.values() {
+                        let _ = tx.lock().await.send(WsMessage::Text(payload.clone())).await;
+                    }
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    start.elapsed()
+}
+
+/// The current broadcast, timed until every fast client has been written to.
+async fn bench_registry(clients: usize, with_slow: bool, delivered: &Arc<AtomicU64>) -> Duration {
+    let metrics = Arc::new(Metrics::new());
+    let registry: Clients = Arc::new(ClientRegistry::new());
+    let mut writers = Vec::with_capacity(clients);
+    for i in 0..clients {
+        let tx = Outbound::new(QUEUE_CAPACITY, SlowConsumerPolicy::DropOldest, Arc::clone(&metrics));
+        writers.push(tokio::spawn(tx.clone().drain_into(client_sink(is_slow(i, with_slow), delivered))));
+        registry.insert(Client {
+            id: Uuid::new_v4(),
+            username: Some("user".to_string()),
+            principal: None,
+            protocol_version: None,
+            tx,
+            last_seen: Arc::new(StdMutex::new(Instant::now())),
+        });
+    }
+    let mut sender_id = Uuid::nil();
+    registry.for_each(|client| sender_id = client.id);
+    let expected = fast_deliveries(clients, with_slow);
+
+    let start = Instant::now();
+    let tasks: Vec<_> = (0..SENDERS)
+        .map(|_| {
+            let registry = Arc::clone(&registry);
+            tokio::spawn(async move {
+                for _ in 0..BROADCASTS_PER_SENDER {
+                    let sender = registry.get(&sender_id).unwrap();
+                    send(&chat(sender.id), &registry);
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    while delivered.load(Ordering::Relaxed) < expected {
+        tokio::time::sleep(Duration::from_micros(200)).await;
+    }
+    let elapsed = start.elapsed();
+
+    registry.for_each(|client| client.tx.close());
+    for writer in writers {
+        writer.abort();
+    }
+    elapsed
+}
+
+fn fast_deliveries(clients: usize, with_slow: bool) -> u64 {
+    let fast = (0..clients).filter(|i| !is_slow(*i, with_slow)).count();
+    (SENDERS * BROADCASTS_PER_SENDER * fast) as u64
+}
+
+fn report(label: &str, clients: usize, with_slow: bool, elapsed: Duration) {
+    println!(
+        "{:<10} {:>6} clients  {:<11} {:>9.2?}  {:>12.0} deliveries/s",
+        label,
+        clients,
+        if with_slow { "1‰ slow" } else { "all fast" },
+        elapsed,
+        fast_deliveries(clients, with_slow) as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    for clients in [1_000, 10_000] {
+        for with_slow in [false, true] {
+            let delivered = Arc::new(AtomicU64::new(0));
+            let before = runtime.block_on(bench_mutex(clients, with_slow, &delivered));
+            report("mutex", clients, with_slow, before);
+
+            let delivered = Arc::new(AtomicU64::new(0));
+            let after = runtime.block_on(bench_registry(clients, with_slow, &delivered));
+            report("registry", clients, with_slow, after);
+        }
+    }
+}
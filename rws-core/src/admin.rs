@@ -0,0 +1,231 @@
+//! Operator HTTP API over live server state. Every request must carry
+//! `Authorization: Bearer <admin token>`.
+//!
+//! | Method | Path | Action |
+//! |---|---|---|
+//! | `GET` | `/clients` | List connected clients |
+//! | `POST` | `/clients/{id}/kick` | Close a client's connection |
+//! | `GET` | `/rooms` | List rooms |
+//! | `GET` | `/rooms/{id}` | Show a room and its members |
+//! | `DELETE` | `/rooms/{id}` | Delete a room |
+//! | `POST` | `/announcements` | Send `{"message": ...}` to every client |
+
+use std::sync::Arc;
+
+use rws_common::{EventMessage, UserInfo};
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use uuid::Uuid;
+
+use crate::{
+    client::{Client, Clients},
+    http::{Handler, Request, Response},
+    room::{Room, SharedRoomManager},
+    util::broadcast::send,
+};
+
+/// Longest kick reason, in bytes, that fits in a close frame after its
+/// 2-byte code.
+const MAX_CLOSE_REASON: usize = 123;
+
+#[derive(Debug, Serialize)]
+pub struct ClientSummary {
+    pub id: Uuid,
+    pub username: Option<String>,
+    pub authenticated: bool,
+    pub rooms: Vec<Uuid>,
+    pub idle_secs: u64,
+    pub queued_frames: usize,
+    pub dropped_frames: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoomSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub owner_id: Uuid,
+    pub member_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoomDetail {
+    #[serde(flatten)]
+    pub room: RoomSummary,
+    pub members: Vec<UserInfo>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct KickRequest {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnnouncementRequest {
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+struct Admin {
+    clients: Clients,
+    room_manager: SharedRoomManager,
+    token: String,
+}
+
+pub(crate) fn handler(clients: Clients, room_manager: SharedRoomManager, token: String) -> Handler {
+    let admin = Arc::new(Admin {
+        clients,
+        room_manager,
+        token,
+    });
+    Arc::new(move |request| {
+        let response = admin.handle(request);
+        Box::pin(async move { response })
+    })
+}
+
+impl Admin {
+    fn handle(&self, request: Request) -> Response {
+        if !self.authorized(&request) {
+            return error(401, "missing or invalid admin token");
+        }
+
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["clients"]) => Response::json(200, &self.list_clients()),
+            ("POST", ["clients", id, "kick"]) => match parse_id(id) {
+                Some(id) => self.kick(id, &request),
+                None => error(400, "invalid client id"),
+            },
+            ("GET", ["rooms"]) => Response::json(200, &self.list_rooms()),
+            ("GET", ["rooms", id]) => match parse_id(id).and_then(|id| self.room_manager.get_room(&id)) {
+                Some(room) => Response::json(200, &self.room_detail(room)),
+                None => error(404, "room not found"),
+            },
+            ("DELETE", ["rooms", id]) => match parse_id(id) {
+                Some(id) if self.room_manager.handle_delete_room(&self.clients, id) => Response::text(204, ""),
+                Some(_) => error(404, "room not found"),
+                None => error(400, "invalid room id"),
+            },
+            ("POST", ["announcements"]) => self.announce(&request),
+            (_, ["clients" | "rooms" | "announcements", ..]) => error(405, "method not allowed"),
+            _ => error(404, "not found"),
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), self.token.as_bytes()))
+    }
+
+    fn list_clients(&self) -> Vec<ClientSummary> {
+        let mut clients = Vec::with_capacity(self.clients.len());
+        self.clients.for_each(|client| clients.push(self.client_summary(client)));
+        clients
+    }
+
+    fn client_summary(&self, client: &Client) -> ClientSummary {
+        ClientSummary {
+            id: client.id,
+            username: client.username.clone(),
+            authenticated: client.principal.is_some(),
+            rooms: self.room_manager.get_user_rooms(&client.id),
+            idle_secs: client.idle_for().as_secs(),
+            queued_frames: client.tx.len(),
+            dropped_frames: client.tx.dropped(),
+        }
+    }
+
+    fn list_rooms(&self) -> Vec<RoomSummary> {
+        self.room_manager
+            .rooms
+            .iter()
+            .map(|room| room_summary(room.value()))
+            .collect()
+    }
+
+    fn room_detail(&self, room: Room) -> RoomDetail {
+        let members = room
+            .members
+            .iter()
+            .map(|id| UserInfo {
+                id: *id,
+                username: self.clients.username(id).unwrap_or_default(),
+            })
+            .collect();
+        RoomDetail {
+            room: room_summary(&room),
+            members,
+        }
+    }
+
+    fn kick(&self, id: Uuid, request: &Request) -> Response {
+        let kick: KickRequest = if request.body.is_empty() {
+            KickRequest::default()
+        } else {
+            match serde_json::from_slice(&request.body) {
+                Ok(kick) => kick,
+                Err(e) => return error(400, &format!("invalid body: {}", e)),
+            }
+        };
+
+        if kick.reason.as_ref().is_some_and(|reason| reason.len() > MAX_CLOSE_REASON) {
+            return error(400, &format!("reason is limited to {} bytes", MAX_CLOSE_REASON));
+        }
+
+        let Some(client) = self.clients.get(&id) else {
+            return error(404, "client not found");
+        };
+
+        let reason = kick.reason.unwrap_or_else(|| "kicked by operator".to_string());
+        tracing::info!(client_id = %id, reason = %reason, "admin kicked client");
+        // The connection task sees the closed queue and runs the normal disconnect path
+        client.tx.close_with(CloseFrame {
+            code: CloseCode::Policy,
+            reason: reason.into(),
+        });
+        Response::text(204, "")
+    }
+
+    fn announce(&self, request: &Request) -> Response {
+        let announcement: AnnouncementRequest = match serde_json::from_slice(&request.body) {
+            Ok(announcement) => announcement,
+            Err(e) => return error(400, &format!("invalid body: {}", e)),
+        };
+
+        tracing::info!(recipients = self.clients.len(), "admin announcement");
+        send(
+            &EventMessage::Announcement {
+                message: announcement.message,
+            },
+            &self.clients,
+        );
+        Response::text(204, "")
+    }
+}
+
+fn room_summary(room: &Room) -> RoomSummary {
+    RoomSummary {
+        id: room.id,
+        name: room.name.clone(),
+        owner_id: room.owner_id,
+        member_count: room.members.len(),
+    }
+}
+
+fn parse_id(id: &str) -> Option<Uuid> {
+    Uuid::parse_str(id).ok()
+}
+
+fn error(status: u16, message: &str) -> Response {
+    Response::json(status, &ErrorBody { error: message })
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,228 @@
+use std::{fmt, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request},
+    http::{header, StatusCode},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The identity established for a connection during the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// Stable account identifier issued by the token authority.
+    pub subject: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken(String),
+    Expired,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "missing bearer token"),
+            AuthError::InvalidToken(reason) => write!(f, "invalid token: {}", reason),
+            AuthError::Expired => write!(f, "token expired"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl AuthError {
+    /// HTTP response used to refuse the WebSocket upgrade.
+    pub fn to_response(&self) -> ErrorResponse {
+        let mut response = ErrorResponse::new(Some(self.to_string()));
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+        response
+    }
+}
+
+/// Validates the handshake request of an incoming connection.
+///
+/// Called synchronously from the WebSocket handshake; returning an error
+/// refuses the upgrade with `401 Unauthorized`.
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, request: &Request) -> Result<Principal, AuthError>;
+}
+
+/// Extract a token from `Authorization: Bearer <token>` or a `token` query parameter.
+pub fn request_token(request: &Request) -> Option<String> {
+    let from_header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|t| t.trim().to_string());
+
+    from_header.or_else(|| {
+        request.uri().query().and_then(|query| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "token")
+                .map(|(_, value)| value.to_string())
+        })
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    name: String,
+    exp: u64,
+}
+
+/// Authenticator for HMAC-SHA256 signed tokens of the form
+/// `base64url(claims).base64url(signature)`.
+#[derive(Clone)]
+pub struct HmacAuthenticator {
+    key: Vec<u8>,
+}
+
+impl HmacAuthenticator {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+
+    /// Issue a token for `subject` that is valid for `ttl`.
+    pub fn issue(&self, subject: &str, username: &str, ttl: Duration) -> String {
+        let claims = Claims {
+            sub: subject.to_string(),
+            name: username.to_string(),
+            exp: (unix_now() + ttl).as_secs(),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).expect("claims serialize"));
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        format!("{}.{}", payload, signature)
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Principal, AuthError> {
+        let (payload, signature) = token
+            .split_once('.')
+            .ok_or_else(|| AuthError::InvalidToken("malformed token".to_string()))?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| AuthError::InvalidToken("malformed signature".to_string()))?;
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| AuthError::InvalidToken("bad signature".to_string()))?;
+
+        let claims: Claims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| AuthError::InvalidToken("malformed claims".to_string()))?;
+
+        if claims.exp <= unix_now().as_secs() {
+            return Err(AuthError::Expired);
+        }
+
+        Ok(Principal {
+            subject: claims.sub,
+            username: claims.name,
+        })
+    }
+}
+
+impl Authenticator for HmacAuthenticator {
+    fn authenticate(&self, request: &Request) -> Result<Principal, AuthError> {
+        let token = request_token(request).ok_or(AuthError::MissingToken)?;
+        self.verify(&token)
+    }
+}
+
+fn unix_now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, authorization: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(value) = authorization {
+            builder = builder.header(header::AUTHORIZATION, value);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn issued_tokens_verify() {
+        let auth = HmacAuthenticator::new("secret");
+        let token = auth.issue("user-1", "alice", Duration::from_secs(60));
+        let principal = auth.verify(&token).unwrap();
+        assert_eq!(principal, Principal { subject: "user-1".into(), username: "alice".into() });
+    }
+
+    #[test]
+    fn tokens_signed_with_another_key_are_refused() {
+        let token = HmacAuthenticator::new("other").issue("user-1", "alice", Duration::from_secs(60));
+        let error = HmacAuthenticator::new("secret").verify(&token).unwrap_err();
+        assert_eq!(error, AuthError::InvalidToken("bad signature".into()));
+    }
+
+    #[test]
+    fn tampered_payloads_are_refused() {
+        let auth = HmacAuthenticator::new("secret");
+        let token = auth.issue("user-1", "alice", Duration::from_secs(60));
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = Claims { sub: "user-1".into(), name: "admin".into(), exp: u64::MAX };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+
+        let error = auth.verify(&format!("{}.{}", payload, signature)).unwrap_err();
+        assert_eq!(error, AuthError::InvalidToken("bad signature".into()));
+    }
+
+    #[test]
+    fn expired_and_malformed_tokens_are_refused() {
+        let auth = HmacAuthenticator::new("secret");
+        let expired = auth.issue("user-1", "alice", Duration::ZERO);
+        assert_eq!(auth.verify(&expired).unwrap_err(), AuthError::Expired);
+        assert_eq!(auth.verify("no-dot").unwrap_err(), AuthError::InvalidToken("malformed token".into()));
+        assert_eq!(auth.verify("abc.!!!").unwrap_err(), AuthError::InvalidToken("malformed signature".into()));
+    }
+
+    #[test]
+    fn tokens_come_from_the_header_before_the_query() {
+        assert_eq!(request_token(&request("/", Some("Bearer abc"))), Some("abc".into()));
+        assert_eq!(request_token(&request("/?room=x&token=def", None)), Some("def".into()));
+        assert_eq!(request_token(&request("/?token=def", Some("Bearer abc"))), Some("abc".into()));
+        assert_eq!(request_token(&request("/", Some("Basic abc"))), None);
+        assert_eq!(request_token(&request("/?other=1", None)), None);
+    }
+
+    #[test]
+    fn missing_tokens_get_401_with_a_challenge() {
+        let auth = HmacAuthenticator::new("secret");
+        let error = auth.authenticate(&request("/", None)).unwrap_err();
+        assert_eq!(error, AuthError::MissingToken);
+
+        let response = error.to_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+    }
+}
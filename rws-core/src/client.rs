@@ -1,19 +1,141 @@
-use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
-use tokio_tungstenite::WebSocketStream;
-use tokio::net::TcpStream;
-use futures_util::stream::SplitSink;
-use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use dashmap::{mapref::entry::Entry, DashMap};
 use uuid::Uuid;
 
-pub type Tx = Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, WsMessage>>>;
+use crate::{auth::Principal, metrics::Metrics, outbound::Outbound};
 
 #[derive(Debug, Clone)]
 pub struct Client {
     pub id: Uuid,
     pub username : Option<String>,
-    pub tx: Tx,
+    /// Set when the connection authenticated during the handshake.
+    pub principal: Option<Principal>,
+    /// Protocol version agreed through `Hello` or `Sec-WebSocket-Protocol`;
+    /// `None` for clients that never negotiated one.
+    pub protocol_version: Option<u32>,
+    /// Queue drained by the client's dedicated writer task.
+    pub tx: Outbound,
+    /// When the peer last sent any frame, including pongs.
+    pub last_seen: Arc<std::sync::Mutex<Instant>>,
 }
 
-pub type Clients = Arc<Mutex<HashMap<Uuid, Client>>>;
\ No newline at end of file
+impl Client {
+    pub fn touch(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    pub fn idle_for(&self) -> Duration {
+        self.last_seen.lock().unwrap().elapsed()
+    }
+}
+
+/// Concurrent registry of connected clients.
+///
+/// Backed by sharded maps so lookups and broadcasts from different
+/// connections proceed in parallel. Usernames are indexed case-insensitively
+/// so claiming one is atomic.
+#[derive(Debug, Default)]
+pub struct ClientRegistry {
+    clients: DashMap<Uuid, Client>,
+    usernames: DashMap<String, Uuid>,
+    metrics: Arc<Metrics>,
+}
+
+pub type Clients = Arc<ClientRegistry>;
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry that records broadcast failures in `metrics`.
+    pub fn with_metrics(metrics: Arc<Metrics>) -> Self {
+        Self {
+            metrics,
+            ..Self::default()
+        }
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn insert(&self, client: Client) {
+        self.clients.insert(client.id, client);
+    }
+
+    /// Remove a client and release its username.
+    pub fn remove(&self, id: &Uuid) -> Option<Client> {
+        let (_, client) = self.clients.remove(id)?;
+        if let Some(username) = &client.username {
+            self.usernames.remove_if(&username.to_lowercase(), |_, owner| owner == id);
+        }
+        Some(client)
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<Client> {
+        self.clients.get(id).map(|c| c.value().clone())
+    }
+
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.clients.contains_key(id)
+    }
+
+    pub fn username(&self, id: &Uuid) -> Option<String> {
+        self.clients.get(id).and_then(|c| c.username.clone())
+    }
+
+    /// Give `username` to client `id` unless another client holds it (ignoring
+    /// case). Returns the client's previous username on success.
+    pub fn claim_username(&self, id: Uuid, username: &str) -> Result<Option<String>, UsernameTaken> {
+        match self.usernames.entry(username.to_lowercase()) {
+            Entry::Occupied(entry) if *entry.get() != id => return Err(UsernameTaken),
+            Entry::Occupied(_) => {}
+            Entry::Vacant(entry) => {
+                entry.insert(id);
+            }
+        }
+
+        let previous = match self.clients.get_mut(&id) {
+            Some(mut client) => client.username.replace(username.to_string()),
+            None => {
+                self.usernames.remove_if(&username.to_lowercase(), |_, owner| *owner == id);
+                return Ok(None);
+            }
+        };
+
+        if let Some(old) = &previous
+            && old.to_lowercase() != username.to_lowercase()
+        {
+            self.usernames.remove_if(&old.to_lowercase(), |_, owner| *owner == id);
+        }
+
+        Ok(previous)
+    }
+
+    pub fn set_protocol_version(&self, id: &Uuid, version: u32) {
+        if let Some(mut client) = self.clients.get_mut(id) {
+            client.protocol_version = Some(version);
+        }
+    }
+
+    /// Call `f` for every connected client.
+    pub fn for_each(&self, mut f: impl FnMut(&Client)) {
+        for entry in self.clients.iter() {
+            f(entry.value());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+}
+
+/// Returned by [`ClientRegistry::claim_username`] when the name is in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsernameTaken;
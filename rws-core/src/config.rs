@@ -0,0 +1,204 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::outbound::SlowConsumerPolicy;
+
+/// Tunable limits and policies for a [`crate::Server`].
+///
+/// Every section deserializes with defaults for missing keys and rejects
+/// unknown ones, so it can be loaded straight from a config file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub username: UsernameRules,
+    pub heartbeat: HeartbeatConfig,
+    pub outbound: OutboundConfig,
+    pub shutdown: ShutdownConfig,
+    pub limits: LimitsConfig,
+    pub logging: LoggingConfig,
+}
+
+impl ServerConfig {
+    /// Check values that would make the server misbehave rather than fail.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.heartbeat.interval.is_zero() {
+            return Err("heartbeat.interval_secs must be greater than 0".to_string());
+        }
+        if self.heartbeat.timeout <= self.heartbeat.interval {
+            return Err("heartbeat.timeout_secs must be greater than heartbeat.interval_secs".to_string());
+        }
+        if self.outbound.capacity == 0 {
+            return Err("outbound.capacity must be greater than 0".to_string());
+        }
+        if self.username.min_len == 0 || self.username.min_len > self.username.max_len {
+            return Err("username.min_len must be between 1 and username.max_len".to_string());
+        }
+        if self.limits.max_message_size == 0 {
+            return Err("limits.max_message_size must be greater than 0".to_string());
+        }
+        if self.limits.max_room_name_len == 0 {
+            return Err("limits.max_room_name_len must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Size and capacity limits. Exceeding a room limit is reported to the client
+/// as `LimitExceeded`; oversized frames close the connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Largest WebSocket message or frame accepted from a client, in bytes.
+    pub max_message_size: usize,
+    pub max_rooms: usize,
+    pub max_room_members: usize,
+    pub max_room_name_len: usize,
+    pub max_room_topic_len: usize,
+    /// Malformed or unsupported frames tolerated from one connection before
+    /// it is closed. `0` never closes.
+    pub max_protocol_violations: u32,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: 64 * 1024,
+            max_rooms: 1_000,
+            max_room_members: 1_000,
+            max_room_name_len: 64,
+            max_room_topic_len: 256,
+            max_protocol_violations: 10,
+        }
+    }
+}
+
+/// What may appear in logs. Chat content is redacted unless `log_content`
+/// is enabled, so production logs do not leak message text.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub log_content: bool,
+}
+
+impl LoggingConfig {
+    /// `content` if logging it is allowed, otherwise a placeholder with its size.
+    pub fn content(&self, content: &str) -> String {
+        if self.log_content {
+            content.to_string()
+        } else {
+            format!("<redacted {} bytes>", content.len())
+        }
+    }
+}
+
+/// How long a graceful shutdown waits for clients' queues to drain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    #[serde(rename = "drain_timeout_secs", with = "secs")]
+    pub drain_timeout: Duration,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Per-client outbound queue size and what happens when it fills up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboundConfig {
+    pub capacity: usize,
+    pub policy: SlowConsumerPolicy,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 256,
+            policy: SlowConsumerPolicy::default(),
+        }
+    }
+}
+
+/// WebSocket ping schedule and the deadline after which a silent peer is
+/// disconnected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeartbeatConfig {
+    #[serde(rename = "interval_secs", with = "secs")]
+    pub interval: Duration,
+    #[serde(rename = "timeout_secs", with = "secs")]
+    pub timeout: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(45),
+        }
+    }
+}
+
+/// Validation rules applied to usernames on `Join` and `ChangeUsername`.
+///
+/// Uniqueness is always enforced, case-insensitively, across connected clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsernameRules {
+    pub min_len: usize,
+    pub max_len: usize,
+    /// Characters allowed in addition to ASCII letters and digits.
+    pub extra_chars: String,
+}
+
+impl Default for UsernameRules {
+    fn default() -> Self {
+        Self {
+            min_len: 2,
+            max_len: 32,
+            extra_chars: "_-.".to_string(),
+        }
+    }
+}
+
+impl UsernameRules {
+    pub fn validate(&self, username: &str) -> Result<(), String> {
+        let len = username.chars().count();
+        if len < self.min_len || len > self.max_len {
+            return Err(format!(
+                "Username must be between {} and {} characters",
+                self.min_len, self.max_len
+            ));
+        }
+
+        if let Some(c) = username
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && !self.extra_chars.contains(*c))
+        {
+            return Err(format!("Username contains disallowed character '{}'", c));
+        }
+
+        Ok(())
+    }
+}
+
+/// Durations are written as whole seconds in config files.
+mod secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
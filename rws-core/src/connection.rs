@@ -0,0 +1,403 @@
+use std::{
+    fmt, io,
+    sync::Arc,
+    time::Instant,
+};
+
+use futures_util::StreamExt;
+use tracing::Instrument;
+use rws_common::{parse_subprotocol, subprotocol, ErrorCode, EventMessage, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    time::MissedTickBehavior,
+};
+use tokio_tungstenite::{
+    accept_hdr_async_with_config,
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue, StatusCode},
+        protocol::{frame::coding::CloseCode, CloseFrame, WebSocketConfig},
+        Error as WsError,
+        Message as WsMessage,
+    },
+};
+
+use crate::{
+    auth::{AuthError, Authenticator},
+    client::{Client, Clients},
+    config::ServerConfig,
+    dispatcher::{self, dispatch},
+    handler,
+    hooks::{EventHandlers, HookContext},
+    http::{self, Rewind},
+    metrics::Metrics,
+    outbound::{Outbound, SendError},
+    room::SharedRoomManager,
+    shutdown::{self, ShutdownHandle},
+    tls::TlsAcceptor,
+    transport::BoxedStream,
+    util::broadcast::{send, send_to_client_instance},
+};
+
+/// Everything a connection task needs from the server.
+#[derive(Clone)]
+pub(crate) struct Shared {
+    pub clients: Clients,
+    pub room_manager: SharedRoomManager,
+    pub handlers: Arc<EventHandlers>,
+    pub authenticator: Option<Arc<dyn Authenticator>>,
+    pub config: Arc<ServerConfig>,
+    pub metrics: Arc<Metrics>,
+    pub shutdown: ShutdownHandle,
+}
+
+/// Why a connection was dropped before it became a client.
+#[derive(Debug)]
+pub(crate) enum HandshakeError {
+    Tls(io::Error),
+    TimedOut,
+    BadRequest(io::Error),
+    NotWebSocket,
+    ShuttingDown,
+    Unauthorized(AuthError),
+    UnsupportedVersion,
+    WebSocket(WsError),
+}
+
+impl HandshakeError {
+    /// Stable snake_case label used in metrics.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            HandshakeError::Tls(_) => "tls",
+            HandshakeError::TimedOut => "timed_out",
+            HandshakeError::BadRequest(_) => "bad_request",
+            HandshakeError::NotWebSocket => "not_websocket",
+            HandshakeError::ShuttingDown => "shutting_down",
+            HandshakeError::Unauthorized(_) => "unauthorized",
+            HandshakeError::UnsupportedVersion => "unsupported_version",
+            HandshakeError::WebSocket(_) => "websocket",
+        }
+    }
+}
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandshakeError::Tls(e) => write!(f, "TLS handshake failed: {}", e),
+            HandshakeError::TimedOut => write!(f, "handshake timed out"),
+            HandshakeError::BadRequest(e) => write!(f, "unreadable request: {}", e),
+            HandshakeError::NotWebSocket => write!(f, "not a WebSocket upgrade"),
+            HandshakeError::ShuttingDown => write!(f, "server is shutting down"),
+            HandshakeError::Unauthorized(e) => write!(f, "rejected: {}", e),
+            HandshakeError::UnsupportedVersion => write!(f, "no supported protocol version offered"),
+            HandshakeError::WebSocket(e) => write!(f, "WebSocket handshake failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HandshakeError {}
+
+/// Complete the TLS handshake when `tls` is set, then serve the connection.
+/// Peers that stall the handshake are dropped after the heartbeat timeout.
+/// Connections that never become clients are logged and counted by reason.
+pub(crate) async fn accept(stream: BoxedStream, tls: Option<TlsAcceptor>, shared: Shared) {
+    let metrics = Arc::clone(&shared.metrics);
+    let Err(e) = handshake(stream, tls, shared).await else { return };
+
+    match e {
+        HandshakeError::Tls(_) | HandshakeError::WebSocket(_) => tracing::warn!(reason = e.kind(), error = %e, "handshake failed"),
+        HandshakeError::Unauthorized(_) => tracing::info!(reason = e.kind(), error = %e, "handshake failed"),
+        _ => tracing::debug!(reason = e.kind(), error = %e, "handshake failed"),
+    }
+    metrics.record_handshake_failure(e.kind());
+}
+
+async fn handshake(stream: BoxedStream, tls: Option<TlsAcceptor>, shared: Shared) -> Result<(), HandshakeError> {
+    match tls {
+        Some(acceptor) => match tokio::time::timeout(shared.config.heartbeat.timeout, acceptor.accept(stream)).await {
+            Ok(Ok(stream)) => route(stream, shared).await,
+            Ok(Err(e)) => Err(HandshakeError::Tls(e)),
+            Err(_) => Err(HandshakeError::TimedOut),
+        },
+        None => route(stream, shared).await,
+    }
+}
+
+/// Read the request head and either answer it as a plain HTTP request
+/// (health probes) or replay it into the WebSocket handshake.
+async fn route<S>(mut stream: S, shared: Shared) -> Result<(), HandshakeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (request, raw) = match tokio::time::timeout(shared.config.heartbeat.timeout, http::read_head(&mut stream)).await {
+        Ok(Ok((request, raw, _))) => (request, raw),
+        Ok(Err(e)) => {
+            let _ = http::Response::text(400, "bad request\n").write_to(&mut stream).await;
+            return Err(HandshakeError::BadRequest(e));
+        }
+        Err(_) => return Err(HandshakeError::TimedOut),
+    };
+
+    if !request.is_websocket_upgrade() {
+        let response = probe_response(&request, &shared.shutdown);
+        let _ = response.write_to(&mut stream).await;
+        return match response.status {
+            426 => Err(HandshakeError::NotWebSocket),
+            _ => Ok(()),
+        };
+    }
+
+    if shared.shutdown.is_triggered() {
+        let _ = http::Response::text(503, "shutting down\n").write_to(&mut stream).await;
+        return Err(HandshakeError::ShuttingDown);
+    }
+
+    serve(Rewind::new(raw, stream), shared).await
+}
+
+/// `/healthz` answers while the process is up; `/readyz` turns 503 once
+/// shutdown begins so load balancers stop routing new clients here.
+fn probe_response(request: &http::Request, shutdown: &ShutdownHandle) -> http::Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/healthz") => http::Response::text(200, "ok\n"),
+        ("GET", "/readyz") if shutdown.is_triggered() => http::Response::text(503, "shutting down\n"),
+        ("GET", "/readyz") => http::Response::text(200, "ready\n"),
+        _ => http::Response::text(426, "WebSocket upgrade required\n"),
+    }
+}
+
+/// Upgrade `stream` to a WebSocket and serve it until the peer leaves, times
+/// out, or the server shuts down. Returns once the writer has drained, or
+/// straight away if the upgrade fails.
+///
+/// Everything logged while serving, including from handlers, is recorded in a
+/// `connection` span carrying the client id; handlers fill in `username` and
+/// the most recently entered `room`.
+pub(crate) async fn serve<S>(stream: S, shared: Shared) -> Result<(), HandshakeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let id = uuid::Uuid::new_v4();
+    let span = tracing::info_span!(
+        "connection",
+        client_id = %id,
+        username = tracing::field::Empty,
+        room = tracing::field::Empty,
+    );
+    serve_connection(id, stream, shared).instrument(span).await
+}
+
+async fn serve_connection<S>(id: uuid::Uuid, stream: S, shared: Shared) -> Result<(), HandshakeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut principal = None;
+    let mut negotiated = None;
+    let mut rejection = None;
+    // The error type is dictated by tungstenite's handshake callback
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, mut response: Response| -> Result<Response, ErrorResponse> {
+        if let Some(authenticator) = &shared.authenticator {
+            match authenticator.authenticate(request) {
+                Ok(p) => principal = Some(p),
+                Err(e) => {
+                    let response = e.to_response();
+                    rejection = Some(HandshakeError::Unauthorized(e));
+                    return Err(response);
+                }
+            }
+        }
+
+        match negotiate_subprotocol(request) {
+            Ok(Some(version)) => {
+                if let Ok(value) = HeaderValue::from_str(&subprotocol(version)) {
+                    response.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, value);
+                }
+                negotiated = Some(version);
+            }
+            Ok(None) => {}
+            Err(refusal) => {
+                rejection = Some(HandshakeError::UnsupportedVersion);
+                return Err(refusal);
+            }
+        }
+        Ok(response)
+    };
+
+    // WebSocket upgrade
+    let ws_config = WebSocketConfig {
+        max_message_size: Some(shared.config.limits.max_message_size),
+        max_frame_size: Some(shared.config.limits.max_message_size),
+        ..WebSocketConfig::default()
+    };
+    let ws_stream = match accept_hdr_async_with_config(stream, callback, Some(ws_config)).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => return Err(rejection.unwrap_or(HandshakeError::WebSocket(e))),
+    };
+
+    let config = shared.config;
+
+    tracing::info!("client connected");
+    shared.metrics.record_connection();
+    let (write, mut read) = ws_stream.split();
+    let tx = Outbound::new(config.outbound.capacity, config.outbound.policy, Arc::clone(&shared.metrics));
+    let writer = tokio::spawn(tx.clone().drain_into(write));
+    let client = Client {
+        id,
+        username: None,
+        principal,
+        protocol_version: negotiated,
+        tx,
+        last_seen: Arc::new(std::sync::Mutex::new(Instant::now())),
+    };
+
+    shared.clients.insert(client.clone());
+
+    // Authenticated connections are named by their token rather than by `Join`
+    if let Some(principal) = &client.principal {
+        if shared.clients.claim_username(id, &principal.username).is_err() {
+            tracing::info!(username = %principal.username, "refusing connection: username in use");
+            shared.clients.remove(&id);
+            let error = ErrorCode::UsernameTaken {
+                message: format!("Username {} is already connected", principal.username),
+            };
+            send_to_client_instance(&client, EventMessage::Error { error });
+            client.tx.close_with(CloseFrame {
+                code: CloseCode::Policy,
+                reason: "username already connected".into(),
+            });
+            let _ = writer.await;
+            return Ok(());
+        }
+        tracing::Span::current().record("username", principal.username.as_str());
+        send(&EventMessage::Join { username: principal.username.clone() }, &shared.clients);
+    }
+
+    if let Some(version) = negotiated {
+        send_to_client_instance(&client, handler::welcome(id, version, &config));
+    }
+
+    // Shutdown began while we were upgrading; the server has already closed
+    // everyone it knew about, so close ourselves
+    if shared.shutdown.is_triggered() {
+        client.tx.close_with(shutdown::close_frame());
+    }
+
+    let ctx = HookContext {
+        sender_id: id,
+        clients: shared.clients,
+        room_manager: shared.room_manager,
+        config,
+        metrics: shared.metrics,
+    };
+    let handlers = shared.handlers;
+    handlers.connected(ctx.clone()).await;
+
+    let mut heartbeat = tokio::time::interval(ctx.config.heartbeat.interval);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    heartbeat.tick().await;
+
+    let mut violations = 0;
+    loop {
+        tokio::select! {
+            msg = read.next() => {
+                let Some(Ok(msg)) = msg else { break };
+                // Any frame, including pongs, proves the peer is alive
+                client.touch();
+                ctx.metrics.record_received(msg.len());
+
+                match decode(&msg, &handlers) {
+                    Ok(Some(event)) => dispatch(event, ctx.clone(), &handlers).await,
+                    Ok(None) => {}
+                    Err(error) => {
+                        violations += 1;
+                        tracing::debug!(error = ?error, violations, "protocol violation");
+                        send_to_client_instance(&client, EventMessage::Error { error });
+
+                        let limit = ctx.config.limits.max_protocol_violations;
+                        if limit > 0 && violations >= limit {
+                            tracing::info!(violations, "closing connection after repeated protocol violations");
+                            client.tx.close_with(CloseFrame {
+                                code: CloseCode::Policy,
+                                reason: "too many protocol violations".into(),
+                            });
+                            break;
+                        }
+                    }
+                }
+            }
+            _ = heartbeat.tick() => {
+                if client.idle_for() > ctx.config.heartbeat.timeout {
+                    tracing::info!("client timed out");
+                    break;
+                }
+                if client.tx.push(WsMessage::Ping(Vec::new())) == Err(SendError::Closed) {
+                    break;
+                }
+            }
+            _ = client.tx.closed() => {
+                tracing::debug!("outbound queue closed");
+                break;
+            }
+        }
+    }
+
+    handler::handle_disconnect(ctx, &handlers).await;
+    let _ = writer.await;
+    Ok(())
+}
+
+/// Pick the newest protocol version offered in `Sec-WebSocket-Protocol`.
+/// Requests that offer none of ours are served without one; requests that
+/// offer only versions we cannot speak are refused.
+#[allow(clippy::result_large_err)]
+fn negotiate_subprotocol(request: &Request) -> Result<Option<u32>, ErrorResponse> {
+    let offered: Vec<u32> = request
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_subprotocol)
+        .collect();
+    if offered.is_empty() {
+        return Ok(None);
+    }
+
+    let supported = MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION;
+    match offered.into_iter().filter(|v| supported.contains(v)).max() {
+        Some(version) => Ok(Some(version)),
+        None => {
+            let mut refusal = ErrorResponse::new(Some(format!(
+                "Unsupported protocol version; this server speaks {} to {}",
+                subprotocol(MIN_PROTOCOL_VERSION),
+                subprotocol(PROTOCOL_VERSION)
+            )));
+            *refusal.status_mut() = StatusCode::BAD_REQUEST;
+            Err(refusal)
+        }
+    }
+}
+
+/// Parse a data frame into an event the server accepts from clients. Control
+/// frames carry no event.
+fn decode(msg: &WsMessage, handlers: &EventHandlers) -> Result<Option<EventMessage>, ErrorCode> {
+    let event = match msg {
+        WsMessage::Text(text) => serde_json::from_str::<EventMessage>(text).map_err(|e| ErrorCode::MalformedMessage {
+            message: e.to_string(),
+            line: e.line(),
+            column: e.column(),
+        })?,
+        WsMessage::Binary(_) => {
+            return Err(ErrorCode::UnsupportedEvent {
+                message: "Binary frames are not supported".to_string(),
+            });
+        }
+        _ => return Ok(None),
+    };
+
+    match dispatcher::unsupported(&event, handlers) {
+        Some(error) => Err(error),
+        None => Ok(Some(event)),
+    }
+}
@@ -0,0 +1,138 @@
+use std::{sync::Arc, time::Instant};
+
+use rws_common::{ErrorCode, EventMessage, UserInfo};
+
+use crate::{handler, hooks::{EventHandlers, HookContext}, util::broadcast::send_to_client};
+
+/// Run the built-in handling for `message`, then any hook registered for it
+/// if that handling accepted the event, recording how long both took.
+///
+/// Identity is taken from the connection (`ctx.sender_id`) only. Messages that
+/// claim a different user are rejected with [`ErrorCode::IdentityMismatch`].
+pub async fn dispatch(message: EventMessage, ctx: HookContext, handlers: &EventHandlers) {
+    let started = Instant::now();
+    let metrics = Arc::clone(&ctx.metrics);
+    dispatch_event(message, ctx, handlers).await;
+    metrics.observe_dispatch(started.elapsed());
+}
+
+async fn dispatch_event(message: EventMessage, ctx: HookContext, handlers: &EventHandlers) {
+    let sender_id = ctx.sender_id;
+    let clients = &ctx.clients;
+    let room_manager = &ctx.room_manager;
+
+    if let Some(claimed) = claimed_identity(&message)
+        && claimed.id != sender_id
+    {
+        tracing::warn!(claimed = %claimed.id, "payload identity does not match connection");
+
+        let error_event = EventMessage::Error {
+            error: ErrorCode::IdentityMismatch {
+                message: format!("Payload identity {} does not match connection", claimed.id),
+            },
+        };
+
+        send_to_client(clients, sender_id, error_event);
+        return;
+    }
+
+    let accepted = match message.clone() {
+        EventMessage::Hello { protocol_version, client_name, .. } => handler::handle_hello(protocol_version, client_name, &ctx),
+        EventMessage::Join { username } => handler::handle_join(username, sender_id, clients, &ctx.config).await,
+        EventMessage::ChangeUsername { username, .. } => handler::handle_change_username(username, sender_id, clients, &ctx.config).await,
+        EventMessage::Chat { id, content, scope, .. } => handler::handle_chat(id, content, scope, &ctx).await,
+        EventMessage::Ping => {
+            tracing::trace!("application ping");
+            true
+        }
+        EventMessage::CreateRoom { room_name, topic, visibility, .. } => {
+            room_manager.handle_create_room(clients, sender_id, room_name, topic, visibility, &ctx.config.limits).await
+        }
+        EventMessage::ListRooms { filter, cursor } => room_manager.handle_list_rooms(clients, sender_id, filter, cursor),
+        EventMessage::GetRoomMembers { room } => room_manager.handle_get_room_members(clients, sender_id, room.id),
+        EventMessage::SetRoomRole { room, user_id, role } => {
+            room_manager.handle_set_room_role(clients, sender_id, room.id, user_id, role)
+        }
+        EventMessage::KickMember { room, user_id, reason } => {
+            room_manager.handle_kick_member(clients, sender_id, room.id, user_id, reason)
+        }
+        EventMessage::BanMember { room, user_id, reason, duration_secs } => {
+            room_manager.handle_ban_member(clients, sender_id, room.id, user_id, reason, duration_secs)
+        }
+        EventMessage::JoinRoom { room, .. } => {
+            room_manager.handle_join_room(clients, sender_id, room.id, &ctx.config.limits).await
+        }
+        EventMessage::JoinRoomByName { room_name, .. } => {
+            room_manager.handle_join_room_by_name(clients, sender_id, &room_name, &ctx.config.limits).await
+        }
+        EventMessage::LeaveRoom { room, .. } => {
+            let room_id = match room.id.is_nil() {
+                true => handler::implicit_room(&ctx).unwrap_or(room.id),
+                false => room.id,
+            };
+            room_manager.handle_leave_room(clients, sender_id, room_id).await
+        }
+        // Left to hooks; see `unsupported`
+        _ => true,
+    };
+
+    if !accepted {
+        tracing::debug!("event refused; skipping its hook");
+        return;
+    }
+
+    if let Some(hook) = handlers.hook_for(&message) {
+        hook(ctx, message).await;
+    }
+}
+
+/// Why `message` cannot be accepted from a client, if it cannot. Events only
+/// the server sends are unsupported unless a hook is registered for them.
+pub fn unsupported(message: &EventMessage, handlers: &EventHandlers) -> Option<ErrorCode> {
+    let from_client = matches!(
+        message,
+        EventMessage::Hello { .. }
+            | EventMessage::Join { .. }
+            | EventMessage::Chat { .. }
+            | EventMessage::AckRead { .. }
+            | EventMessage::CreateRoom { .. }
+            | EventMessage::ListRooms { .. }
+            | EventMessage::GetRoomMembers { .. }
+            | EventMessage::SetRoomRole { .. }
+            | EventMessage::KickMember { .. }
+            | EventMessage::BanMember { .. }
+            | EventMessage::JoinRoom { .. }
+            | EventMessage::JoinRoomByName { .. }
+            | EventMessage::LeaveRoom { .. }
+            | EventMessage::ChangeUsername { .. }
+            | EventMessage::Ping
+    );
+    if from_client || handlers.hook_for(message).is_some() {
+        return None;
+    }
+    Some(ErrorCode::UnsupportedEvent {
+        message: format!("{} is sent by the server only", event_name(message)),
+    })
+}
+
+/// The serialized `event` tag of `message`.
+fn event_name(message: &EventMessage) -> String {
+    serde_json::to_value(message)
+        .ok()
+        .and_then(|value| value.get("event")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| "event".to_string())
+}
+
+/// The user a client-supplied payload claims to come from, if it names one.
+fn claimed_identity(message: &EventMessage) -> Option<&UserInfo> {
+    match message {
+        EventMessage::Chat { sender, .. } => sender.as_ref(),
+        EventMessage::AckRead { reader, .. } => reader.as_ref(),
+        EventMessage::CreateRoom { creator, .. } => creator.as_ref(),
+        EventMessage::JoinRoom { user, .. }
+        | EventMessage::JoinRoomByName { user, .. }
+        | EventMessage::LeaveRoom { user, .. }
+        | EventMessage::ChangeUsername { user, .. } => user.as_ref(),
+        _ => None,
+    }
+}
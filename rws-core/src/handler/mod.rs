@@ -0,0 +1,294 @@
+use crate::{
+    client::Clients, config::ServerConfig, hooks::{EventHandlers, HookContext}, shutdown::{self, ShutdownNotice}, util::{
+        broadcast::{broadcast_to_room, send, send_to_client, send_to_client_instance},
+        get_username_from_client,
+    }
+};
+use rws_common::{
+    capability, ChatScope, ErrorCode, EventMessage, ProtocolLimits, RoomInfo, UserInfo, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+
+pub mod room_handler;
+
+/// First protocol version whose clients name the room each `Chat` and
+/// `LeaveRoom` targets. Older clients were limited to one room, so their
+/// untargeted events go to the room they joined most recently.
+const ROOM_TARGETS_VERSION: u32 = 2;
+
+/// First protocol version that knows the `Moderator` room role.
+const MODERATION_VERSION: u32 = 3;
+
+/// Features advertised to every client in `Welcome`.
+const CAPABILITIES: &[&str] = &[capability::ROOMS, capability::RENAME, capability::ROOM_LIST, capability::MODERATION];
+
+/// Answer `Hello` with `Welcome`, or refuse a protocol version this server
+/// cannot speak and close the connection. Returns whether it was accepted.
+pub fn handle_hello(protocol_version: u32, client_name: String, ctx: &HookContext) -> bool {
+    let Some(client) = ctx.clients.get(&ctx.sender_id) else { return false };
+    tracing::info!(client_name = %client_name, protocol_version, "client said hello");
+
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&protocol_version) {
+        let error = ErrorCode::UnsupportedVersion {
+            message: format!("Protocol version {} is not supported", protocol_version),
+            min_version: MIN_PROTOCOL_VERSION,
+            max_version: PROTOCOL_VERSION,
+        };
+        send_to_client_instance(&client, EventMessage::Error { error });
+        client.tx.close_with(CloseFrame {
+            code: CloseCode::Protocol,
+            reason: "unsupported protocol version".into(),
+        });
+        return false;
+    }
+
+    ctx.clients.set_protocol_version(&ctx.sender_id, protocol_version);
+    send_to_client_instance(&client, welcome(ctx.sender_id, protocol_version, &ctx.config));
+    true
+}
+
+/// Describe this server to client `user_id`, speaking `protocol_version`.
+pub fn welcome(user_id: uuid::Uuid, protocol_version: u32, config: &ServerConfig) -> EventMessage {
+    EventMessage::Welcome {
+        user_id,
+        protocol_version,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        limits: ProtocolLimits {
+            max_message_size: config.limits.max_message_size,
+            max_room_name_len: config.limits.max_room_name_len,
+            username_min_len: config.username.min_len,
+            username_max_len: config.username.max_len,
+        },
+    }
+}
+
+/// Name the sender, returning whether the username was taken on.
+pub async fn handle_join(
+    username: String,
+    sender_id: uuid::Uuid,
+    clients: &Clients,
+    config: &ServerConfig,
+) -> bool {
+    let Some(client) = clients.get(&sender_id) else { return false };
+
+    // Authenticated connections were named from their token when accepted
+    if client.principal.is_some() {
+        send_to_client_instance(&client, EventMessage::AssignedId { user_id: sender_id });
+        return true;
+    }
+
+    // Renames go through `ChangeUsername` so peers learn the old name
+    if let Some(current) = &client.username {
+        let error = ErrorCode::PermissionDenied {
+            message: format!("Already joined as {}; use ChangeUsername to rename", current),
+        };
+        send_to_client_instance(&client, EventMessage::Error { error });
+        return false;
+    }
+
+    if let Err(error) = claim_username(clients, &username, sender_id, config) {
+        send_to_client_instance(&client, EventMessage::Error { error });
+        return false;
+    }
+
+    tracing::Span::current().record("username", username.as_str());
+    tracing::info!(username = %username, "client joined");
+
+    //Assign an ID to the client
+    let id_msg = EventMessage::AssignedId { user_id: sender_id };
+    send_to_client_instance(&client, id_msg);
+
+    // Broadcast the join event to all clients
+    let join_msg = EventMessage::Join { username };
+    send(&join_msg, clients);
+    true
+}
+
+/// Tear down a connection, whether the peer closed it or it timed out: run the
+/// disconnect hook, leave every room (deleting those now empty), drop the client
+/// from the registry and announce the departure to everyone else.
+pub async fn handle_disconnect(ctx: HookContext, handlers: &EventHandlers) {
+    let client_id = ctx.sender_id;
+    handlers.disconnected(ctx.clone()).await;
+
+    for room_id in ctx.room_manager.get_user_rooms(&client_id) {
+        ctx.room_manager.handle_leave_room(&ctx.clients, client_id, room_id).await;
+    }
+
+    let removed = ctx.clients.remove(&client_id);
+    tracing::info!("client disconnected");
+
+    // Let the writer task flush what is queued and exit
+    if let Some(client) = &removed {
+        client.tx.close();
+    }
+
+    if let Some(username) = removed.and_then(|c| c.username) {
+        let presence = EventMessage::Disconnected {
+            user: UserInfo {
+                id: client_id,
+                username,
+            },
+        };
+        send(&presence, &ctx.clients);
+    }
+}
+
+/// Tell every client the server is going away, then close their connections.
+/// Each connection task notices its closed queue and runs the usual disconnect
+/// path while the writer flushes the notice and close frame.
+pub fn handle_shutdown(notice: &ShutdownNotice, clients: &Clients) {
+    tracing::info!(connections = clients.len(), "shutting down");
+
+    let shutdown_msg = EventMessage::ServerShutdown {
+        reconnect_after_secs: notice.reconnect_after.map(|d| d.as_secs()),
+    };
+    send(&shutdown_msg, clients);
+
+    clients.for_each(|client| client.tx.close_with(shutdown::close_frame()));
+}
+
+/// Rename an anonymous client and tell everyone, since every connected client
+/// can see every user through global chat. Returns whether it was renamed.
+pub async fn handle_change_username(
+    username: String,
+    sender_id: uuid::Uuid,
+    clients: &Clients,
+    config: &ServerConfig,
+) -> bool {
+    let Some(client) = clients.get(&sender_id) else { return false };
+
+    let claimed = if client.principal.is_some() {
+        Err(ErrorCode::PermissionDenied {
+            message: "Authenticated users take their username from their token".to_string(),
+        })
+    } else if client.username.is_none() {
+        Err(ErrorCode::PermissionDenied {
+            message: "Join before changing username".to_string(),
+        })
+    } else {
+        claim_username(clients, &username, sender_id, config)
+    };
+
+    let old_username = match claimed {
+        Ok(previous) => previous.unwrap_or_default(),
+        Err(error) => {
+            send_to_client_instance(&client, EventMessage::Error { error });
+            return false;
+        }
+    };
+
+    tracing::Span::current().record("username", username.as_str());
+    tracing::info!(old = %old_username, new = %username, "client renamed");
+
+    let change_msg = EventMessage::ChangeUsername {
+        user: Some(UserInfo {
+            id: sender_id,
+            username: old_username,
+        }),
+        username,
+    };
+    send(&change_msg, clients);
+    true
+}
+
+/// Validate `username` and atomically assign it to `sender_id`, returning the
+/// previous name.
+fn claim_username(
+    clients: &Clients,
+    username: &str,
+    sender_id: uuid::Uuid,
+    config: &ServerConfig,
+) -> Result<Option<String>, ErrorCode> {
+    config
+        .username
+        .validate(username)
+        .map_err(|message| ErrorCode::InvalidUsername { message })?;
+
+    clients
+        .claim_username(sender_id, username)
+        .map_err(|_| ErrorCode::UsernameTaken {
+            message: format!("Username {} is already taken", username),
+        })
+}
+
+/// The room an untargeted event from a pre-multi-room client refers to.
+pub(crate) fn implicit_room(ctx: &HookContext) -> Option<uuid::Uuid> {
+    let legacy = ctx
+        .clients
+        .get(&ctx.sender_id)
+        .is_some_and(|c| c.protocol_version.is_none_or(|v| v < ROOM_TARGETS_VERSION));
+    if !legacy {
+        return None;
+    }
+    ctx.room_manager.get_user_rooms(&ctx.sender_id).last().copied()
+}
+
+/// Deliver a chat to the room named in `scope`, which the sender must belong
+/// to, or to everyone. Returns whether it was delivered.
+pub async fn handle_chat(id: uuid::Uuid, content: String, scope: ChatScope, ctx: &HookContext) -> bool {
+    let sender_id = ctx.sender_id;
+    let clients = &ctx.clients;
+    let room_manager = &ctx.room_manager;
+    tracing::debug!(message_id = %id, content = %ctx.config.logging.content(&content), "chat received");
+
+    let sender = get_username_from_client(clients, sender_id)
+        .unwrap_or_else(|| "Unknown".to_string());
+
+    let room_id = match scope {
+        ChatScope::Room { room } => Some(room.id),
+        ChatScope::Global => implicit_room(ctx),
+    };
+
+    let ack_delivered = EventMessage::AckDelivered { id };
+
+    match room_id {
+        Some(room_id) => {
+            let Some(room) = room_manager.get_room(&room_id).filter(|r| r.members.contains(&sender_id)) else {
+                tracing::debug!(room = %room_id, "chat refused: not a member");
+                send_to_client(clients, sender_id, EventMessage::Error { error: room_handler::not_a_member(room_id) });
+                return false;
+            };
+
+            let scope = ChatScope::Room {
+                room: RoomInfo {
+                    id: room_id,
+                    name: room.name,
+                },
+            };
+            ctx.metrics.record_chat(&scope);
+
+            let chat_msg = EventMessage::Chat {
+                id,
+                sender: Some(UserInfo {
+                    id: sender_id,
+                    username: sender.clone(),
+                }),
+                content,
+                scope,
+            };
+
+            tracing::debug!(message_id = %id, room = %room_id, "broadcasting chat to room");
+
+            broadcast_to_room(&chat_msg, room_id, room_manager, clients);
+        }
+        None => {
+            // If the chat names no room, broadcast to all clients
+            let chat_msg = EventMessage::Chat {
+                id,
+                sender: Some(UserInfo {
+                    id: sender_id,
+                    username: sender.clone(),
+                }),
+                content,
+                scope: ChatScope::Global,
+            };
+            tracing::debug!(message_id = %id, "broadcasting chat globally");
+            ctx.metrics.record_chat(&ChatScope::Global);
+            send(&chat_msg, clients);
+        }
+    }
+    send_to_client(clients, sender_id, ack_delivered);
+    true
+}
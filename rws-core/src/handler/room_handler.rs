@@ -0,0 +1,654 @@
+use rws_common::{ErrorCode, EventMessage, RoomInfo, RoomListing, RoomRole, RoomVisibility, UserInfo};
+use std::time::{Duration, Instant};
+
+use dashmap::mapref::entry::Entry;
+
+use crate::{
+    client::Clients,
+    config::LimitsConfig,
+    room::{self, Ban, RoomCursor, RoomManager},
+    util::{
+        broadcast::{broadcast_to_room, send_to_client},
+        get_username_from_client,
+    },
+};
+
+/// Rooms returned per `RoomList` page.
+const ROOM_LIST_PAGE_SIZE: usize = 50;
+
+// Each `handle_*` returns whether the request was carried out. Refusals are
+// sent to the client as `Error`, and the dispatcher then skips the event's hook.
+impl RoomManager {
+    pub async fn handle_create_room(
+        &self,
+        clients: &Clients,
+        client_id: uuid::Uuid,
+        room_name: String,
+        topic: Option<String>,
+        visibility: RoomVisibility,
+        limits: &LimitsConfig,
+    ) -> bool {
+        let room_id = uuid::Uuid::new_v4();
+
+        if room_name.chars().count() > limits.max_room_name_len {
+            let error_event = EventMessage::Error {
+                error: rws_common::ErrorCode::LimitExceeded {
+                    message: format!("Room names are limited to {} characters", limits.max_room_name_len),
+                },
+            };
+
+            send_to_client(clients, client_id, error_event);
+            return false;
+        }
+
+        if topic.as_ref().is_some_and(|t| t.chars().count() > limits.max_room_topic_len) {
+            let error_event = EventMessage::Error {
+                error: rws_common::ErrorCode::LimitExceeded {
+                    message: format!("Room topics are limited to {} characters", limits.max_room_topic_len),
+                },
+            };
+
+            send_to_client(clients, client_id, error_event);
+            return false;
+        }
+
+        if self.rooms.len() >= limits.max_rooms {
+            let error_event = EventMessage::Error {
+                error: rws_common::ErrorCode::LimitExceeded {
+                    message: format!("The server already has the maximum of {} rooms", limits.max_rooms),
+                },
+            };
+
+            send_to_client(clients, client_id, error_event);
+            return false;
+        }
+
+        let Some(slug) = rws_common::room_slug(&room_name) else {
+            let error_event = EventMessage::Error {
+                error: rws_common::ErrorCode::InvalidRoomName {
+                    message: "Room names need at least one letter or digit".to_string(),
+                },
+            };
+
+            send_to_client(clients, client_id, error_event);
+            return false;
+        };
+
+        // Claim the name first so concurrent creates can't both succeed
+        match self.slugs.entry(slug.clone()) {
+            Entry::Occupied(_) => {
+                tracing::debug!(slug = %slug, "create refused: name taken");
+
+                let error_event = EventMessage::Error {
+                    error: rws_common::ErrorCode::RoomAlreadyExists {
+                        message: format!("A room named #{} already exists", slug),
+                    },
+                };
+
+                send_to_client(clients, client_id, error_event);
+                return false;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(room_id);
+            }
+        }
+
+        let created_room = room::Room {
+            topic,
+            visibility,
+            ..room::Room::new(room_id, room_name.clone(), slug, client_id)
+        };
+
+        self.rooms.insert(room_id, created_room);
+        self.add_membership(client_id, room_id);
+
+        tracing::Span::current().record("room", tracing::field::display(room_id));
+        tracing::info!(room = %room_id, room_name = %room_name, "room created");
+
+        let creator = rws_common::UserInfo {
+            id: client_id,
+            username: get_username_from_client(clients, client_id)
+                .unwrap_or_else(|| "Unknown".to_string()),
+        };
+        let room_info = rws_common::RoomInfo {
+            id: room_id,
+            name: room_name,
+        };
+
+        send_to_client(clients, client_id, EventMessage::RoomCreated { room: room_info.clone() });
+
+        // The creator is the first member
+        let join_event = EventMessage::JoinRoom {
+            user: Some(creator),
+            room: room_info,
+        };
+        send_to_client(clients, client_id, join_event);
+        if let Some(roster) = self.room_members_event(clients, room_id, client_id) {
+            send_to_client(clients, client_id, roster);
+        }
+        true
+    }
+
+    pub async fn handle_join_room(
+        &self,
+        clients: &Clients,
+        client_id: uuid::Uuid,
+        room_id: uuid::Uuid,
+        limits: &LimitsConfig,
+    ) -> bool {
+        let Some(client) = clients.get(&client_id) else {
+            return false;
+        };
+
+        // The room guard must be released before broadcasting, which reads the room again
+        let room_name = match self.rooms.get_mut(&room_id) {
+            Some(mut room) => {
+                if room.members.contains(&client_id) {
+                    None
+                } else if let Some(ban) = room.ban_for(&client) {
+                    let message = match &ban.reason {
+                        Some(reason) => format!("You are banned from {}: {}", room.name, reason),
+                        None => format!("You are banned from {}", room.name),
+                    };
+                    drop(room);
+                    tracing::debug!(room = %room_id, "join refused: banned");
+
+                    let error_event = EventMessage::Error {
+                        error: rws_common::ErrorCode::PermissionDenied { message },
+                    };
+
+                    send_to_client(clients, client_id, error_event);
+                    return false;
+                } else if room.members.len() >= limits.max_room_members {
+                    drop(room);
+
+                    let error_event = EventMessage::Error {
+                        error: rws_common::ErrorCode::LimitExceeded {
+                            message: format!("Room {} is full", room_id),
+                        },
+                    };
+
+                    send_to_client(clients, client_id, error_event);
+                    return false;
+                } else {
+                    room.add_member(client_id);
+                    Some(room.name.clone())
+                }
+            }
+            None => {
+                tracing::debug!(room = %room_id, "join refused: room not found");
+
+                let error_event = EventMessage::Error {
+                    error: rws_common::ErrorCode::RoomNotFound {
+                        message: format!("Room with id {} not found", room_id),
+                    },
+                };
+
+                send_to_client(clients, client_id, error_event);
+                return false;
+            }
+        };
+
+        let Some(room_name) = room_name else {
+            tracing::debug!(room = %room_id, "join refused: already a member");
+
+            let error_event = EventMessage::Error {
+                error: rws_common::ErrorCode::AlreadyInRoom {
+                    message: format!("Client {} is already in room {}", client_id, room_id),
+                },
+            };
+
+            send_to_client(clients, client_id, error_event);
+            return false;
+        };
+
+        self.add_membership(client_id, room_id);
+
+        let join_event = EventMessage::JoinRoom {
+            user: Some(rws_common::UserInfo {
+                id: client_id,
+                username: get_username_from_client(clients, client_id)
+                    .unwrap_or_else(|| "Unknown".to_string()),
+            }),
+            room: rws_common::RoomInfo {
+                id: room_id,
+                name: room_name.clone(),
+            },
+        };
+
+        broadcast_to_room(&join_event, room_id, self, clients);
+        if let Some(roster) = self.room_members_event(clients, room_id, client_id) {
+            send_to_client(clients, client_id, roster);
+        }
+
+        tracing::Span::current().record("room", tracing::field::display(room_id));
+        tracing::info!(room = %room_id, room_name = %room_name, "joined room");
+        true
+    }
+
+    /// Join the public room whose name normalizes the same as `room_name`.
+    pub async fn handle_join_room_by_name(
+        &self,
+        clients: &Clients,
+        client_id: uuid::Uuid,
+        room_name: &str,
+        limits: &LimitsConfig,
+    ) -> bool {
+        let room_id = self
+            .find_by_name(room_name)
+            .filter(|id| self.get_room(id).is_some_and(|room| room.visibility == RoomVisibility::Public));
+
+        match room_id {
+            Some(room_id) => self.handle_join_room(clients, client_id, room_id, limits).await,
+            None => {
+                tracing::debug!(room_name = %room_name, "join refused: no room by that name");
+
+                let error_event = EventMessage::Error {
+                    error: rws_common::ErrorCode::RoomNotFound {
+                        message: format!("No room named {}", room_name),
+                    },
+                };
+
+                send_to_client(clients, client_id, error_event);
+                false
+            }
+        }
+    }
+
+    pub async fn handle_leave_room(
+        &self,
+        clients: &Clients,
+        client_id: uuid::Uuid,
+        room_id: uuid::Uuid,
+    ) -> bool {
+        let left = self.depart(clients, client_id, room_id);
+        if !left {
+            tracing::debug!(room = %room_id, "leave refused: not a member");
+            send_to_client(clients, client_id, EventMessage::Error { error: not_a_member(room_id) });
+        }
+        left
+    }
+
+    /// Take `user_id` out of a room, whether they left or were removed,
+    /// telling everyone who was in it. Ownership passes on if they held it, and
+    /// the room goes once empty. Returns false if they were not a member.
+    fn depart(&self, clients: &Clients, user_id: uuid::Uuid, room_id: uuid::Uuid) -> bool {
+        let (room_name, all_members, new_owner) = match self.rooms.get_mut(&room_id) {
+            Some(mut room) if room.members.contains(&user_id) => {
+                let room_name = room.name.clone();
+                // Get all members before removing the leaving user
+                let all_members: Vec<uuid::Uuid> = room.members.iter().copied().collect();
+                let new_owner = room.remove_member(&user_id);
+                (room_name, all_members, new_owner)
+            }
+            _ => return false,
+        };
+        self.remove_membership(&user_id, &room_id);
+
+        // Remove the room only if nobody joined in the meantime
+        if self.remove_room_if(&room_id, |room| room.members.is_empty()).is_some() {
+            tracing::info!(room = %room_id, "empty room removed");
+        }
+
+        let room_info = rws_common::RoomInfo {
+            id: room_id,
+            name: room_name.clone(),
+        };
+        let leave_event = EventMessage::LeaveRoom {
+            user: Some(user_info(clients, user_id)),
+            room: room_info.clone(),
+        };
+
+        // Send to all members (including the one who left)
+        for member_id in all_members {
+            send_to_client(clients, member_id, leave_event.clone());
+        }
+
+        if let Some(new_owner) = new_owner {
+            let role_event = EventMessage::RoomRoleChanged {
+                room: room_info,
+                user: user_info(clients, new_owner),
+                role: RoomRole::Owner,
+            };
+            self.send_moderation_event(clients, room_id, &role_event);
+            tracing::info!(room = %room_id, owner = %new_owner, "room ownership passed on");
+        }
+
+        tracing::info!(room = %room_id, room_name = %room_name, user = %user_id, "left room");
+        true
+    }
+
+    /// Change a member's role on behalf of the room's owner.
+    pub fn handle_set_room_role(
+        &self,
+        clients: &Clients,
+        client_id: uuid::Uuid,
+        room_id: uuid::Uuid,
+        target: uuid::Uuid,
+        role: RoomRole,
+    ) -> bool {
+        let changed = match self.rooms.get_mut(&room_id) {
+            Some(mut room) if room.members.contains(&client_id) => {
+                if room.owner_id != client_id {
+                    Err(ErrorCode::PermissionDenied {
+                        message: format!("Only the owner of {} can change roles", room.name),
+                    })
+                } else if target == client_id {
+                    Err(ErrorCode::PermissionDenied {
+                        message: "Hand the room to someone else to give up ownership".to_string(),
+                    })
+                } else if !room.members.contains(&target) {
+                    Err(user_not_in_room(target, &room.name))
+                } else {
+                    match role {
+                        RoomRole::Owner => {
+                            room.owner_id = target;
+                            room.moderators.remove(&target);
+                            room.moderators.insert(client_id);
+                        }
+                        RoomRole::Moderator => {
+                            room.moderators.insert(target);
+                        }
+                        RoomRole::Member => {
+                            room.moderators.remove(&target);
+                        }
+                    }
+                    Ok(room.name.clone())
+                }
+            }
+            _ => Err(not_a_member(room_id)),
+        };
+
+        let room_name = match changed {
+            Ok(room_name) => room_name,
+            Err(error) => {
+                send_to_client(clients, client_id, EventMessage::Error { error });
+                return false;
+            }
+        };
+
+        let room_info = RoomInfo {
+            id: room_id,
+            name: room_name,
+        };
+        let mut changes = vec![(target, role)];
+        if role == RoomRole::Owner {
+            changes.push((client_id, RoomRole::Moderator));
+        }
+        for (user_id, role) in changes {
+            let role_event = EventMessage::RoomRoleChanged {
+                room: room_info.clone(),
+                user: user_info(clients, user_id),
+                role,
+            };
+            self.send_moderation_event(clients, room_id, &role_event);
+        }
+
+        tracing::info!(room = %room_id, user = %target, role = ?role, "room role changed");
+        true
+    }
+
+    /// Remove `target` from a room on behalf of one of its owner or moderators.
+    pub fn handle_kick_member(
+        &self,
+        clients: &Clients,
+        client_id: uuid::Uuid,
+        room_id: uuid::Uuid,
+        target: uuid::Uuid,
+        reason: Option<String>,
+    ) -> bool {
+        let room_name = match self.authorize_moderation(client_id, room_id, target, true) {
+            Ok(room_name) => room_name,
+            Err(error) => {
+                send_to_client(clients, client_id, EventMessage::Error { error });
+                return false;
+            }
+        };
+
+        let kicked_event = EventMessage::MemberKicked {
+            room: RoomInfo {
+                id: room_id,
+                name: room_name,
+            },
+            user: user_info(clients, target),
+            by: user_info(clients, client_id),
+            reason,
+        };
+        self.send_moderation_event(clients, room_id, &kicked_event);
+        self.depart(clients, target, room_id);
+
+        tracing::info!(room = %room_id, user = %target, by = %client_id, "member kicked");
+        true
+    }
+
+    /// Keep `target` out of a room, removing them if they are in it, on
+    /// behalf of its owner or one of its moderators.
+    pub fn handle_ban_member(
+        &self,
+        clients: &Clients,
+        client_id: uuid::Uuid,
+        room_id: uuid::Uuid,
+        target: uuid::Uuid,
+        reason: Option<String>,
+        duration_secs: Option<u64>,
+    ) -> bool {
+        let banned = match clients.get(&target) {
+            Some(target_client) => self.authorize_moderation(client_id, room_id, target, false).and_then(|_| {
+                let until = duration_secs.and_then(|secs| Instant::now().checked_add(Duration::from_secs(secs)));
+                let ban = Ban::new(&target_client, reason.clone(), until);
+                // The room may have gone since it was checked
+                let mut room = self.rooms.get_mut(&room_id).ok_or_else(|| not_a_member(room_id))?;
+                room.bans.push(ban);
+                Ok(room.name.clone())
+            }),
+            None => Err(ErrorCode::UserNotFound {
+                message: format!("User {} is not connected", target),
+            }),
+        };
+
+        let room_name = match banned {
+            Ok(room_name) => room_name,
+            Err(error) => {
+                send_to_client(clients, client_id, EventMessage::Error { error });
+                return false;
+            }
+        };
+
+        let banned_event = EventMessage::MemberBanned {
+            room: RoomInfo {
+                id: room_id,
+                name: room_name,
+            },
+            user: user_info(clients, target),
+            by: user_info(clients, client_id),
+            reason,
+            duration_secs,
+        };
+        self.send_moderation_event(clients, room_id, &banned_event);
+        if !self.depart(clients, target, room_id) && knows_moderation(clients, target) {
+            send_to_client(clients, target, banned_event);
+        }
+
+        tracing::info!(room = %room_id, user = %target, by = %client_id, duration_secs = ?duration_secs, "member banned");
+        true
+    }
+
+    /// The room's name if `actor` may kick or ban `target` there. Kicks also
+    /// need `target` to be a member.
+    fn authorize_moderation(
+        &self,
+        actor: uuid::Uuid,
+        room_id: uuid::Uuid,
+        target: uuid::Uuid,
+        target_must_be_member: bool,
+    ) -> Result<String, ErrorCode> {
+        let room = match self.rooms.get(&room_id) {
+            Some(room) if room.members.contains(&actor) => room,
+            _ => return Err(not_a_member(room_id)),
+        };
+
+        if target_must_be_member && !room.members.contains(&target) {
+            Err(user_not_in_room(target, &room.name))
+        } else if !room.can_moderate(&actor, &target) {
+            Err(ErrorCode::PermissionDenied {
+                message: format!("You cannot remove that user from {}", room.name),
+            })
+        } else {
+            Ok(room.name.clone())
+        }
+    }
+
+    /// Send `client_id` one page of the rooms it can see.
+    pub fn handle_list_rooms(
+        &self,
+        clients: &Clients,
+        client_id: uuid::Uuid,
+        filter: Option<String>,
+        cursor: Option<String>,
+    ) -> bool {
+        let after = match cursor.as_deref().map(RoomCursor::decode) {
+            Some(None) => {
+                let error_event = EventMessage::Error {
+                    error: rws_common::ErrorCode::InvalidCursor {
+                        message: "Unrecognised room list cursor".to_string(),
+                    },
+                };
+
+                send_to_client(clients, client_id, error_event);
+                return false;
+            }
+            Some(after) => after,
+            None => None,
+        };
+
+        let (rooms, next) = self.list_rooms(&client_id, filter.as_deref(), after.as_ref(), ROOM_LIST_PAGE_SIZE);
+        let rooms = rooms
+            .into_iter()
+            .map(|room| RoomListing {
+                id: room.id,
+                name: room.name,
+                slug: room.slug,
+                member_count: room.members.len(),
+                topic: room.topic,
+                visibility: room.visibility,
+            })
+            .collect();
+
+        let list_event = EventMessage::RoomList {
+            rooms,
+            next_cursor: next.map(|cursor| cursor.encode()),
+        };
+        send_to_client(clients, client_id, list_event);
+        true
+    }
+
+    /// Send `client_id` the roster of a room it belongs to.
+    pub fn handle_get_room_members(&self, clients: &Clients, client_id: uuid::Uuid, room_id: uuid::Uuid) -> bool {
+        let roster = match self.is_member(&room_id, &client_id) {
+            true => self.room_members_event(clients, room_id, client_id),
+            false => None,
+        };
+        let found = roster.is_some();
+        let event = roster.unwrap_or(EventMessage::Error { error: not_a_member(room_id) });
+        send_to_client(clients, client_id, event);
+        found
+    }
+
+    /// Send a role, kick or ban event to the members of `room_id` that can
+    /// read it; older clients only see the resulting `LeaveRoom`.
+    fn send_moderation_event(&self, clients: &Clients, room_id: uuid::Uuid, event: &EventMessage) {
+        for member_id in self.members(&room_id) {
+            if knows_moderation(clients, member_id) {
+                send_to_client(clients, member_id, event.clone());
+            }
+        }
+    }
+
+    /// A `RoomMembers` snapshot of `room_id` for `viewer`, members ordered by
+    /// username. Clients too old to know moderators see them as members.
+    fn room_members_event(&self, clients: &Clients, room_id: uuid::Uuid, viewer: uuid::Uuid) -> Option<EventMessage> {
+        let room = self.get_room(&room_id)?;
+        let knows_moderators = knows_moderation(clients, viewer);
+
+        let mut members: Vec<UserInfo> = room
+            .members
+            .iter()
+            .map(|id| UserInfo {
+                id: *id,
+                username: get_username_from_client(clients, *id).unwrap_or_else(|| "Unknown".to_string()),
+            })
+            .collect();
+        members.sort_by_key(|m| m.username.to_lowercase());
+        let roles = members
+            .iter()
+            .filter_map(|m| match room.role(&m.id)? {
+                RoomRole::Moderator if !knows_moderators => Some((m.id, RoomRole::Member)),
+                role => Some((m.id, role)),
+            })
+            .collect();
+
+        Some(EventMessage::RoomMembers {
+            room: RoomInfo {
+                id: room_id,
+                name: room.name,
+            },
+            members,
+            roles,
+        })
+    }
+
+    /// Remove a room outright, releasing every member's membership and telling
+    /// them it is gone. Returns false if the room did not exist.
+    pub fn handle_delete_room(&self, clients: &Clients, room_id: uuid::Uuid) -> bool {
+        let Some(room) = self.remove_room(&room_id) else {
+            return false;
+        };
+
+        for member_id in &room.members {
+            self.remove_membership(member_id, &room_id);
+        }
+
+        let deleted_event = EventMessage::RoomDeleted {
+            room: rws_common::RoomInfo {
+                id: room_id,
+                name: room.name.clone(),
+            },
+        };
+        for member_id in &room.members {
+            send_to_client(clients, *member_id, deleted_event.clone());
+        }
+
+        tracing::info!(room = %room_id, room_name = %room.name, members = room.members.len(), "room deleted");
+        true
+    }
+}
+
+/// Sent when a client targets a room it does not belong to, whether or not
+/// the room exists.
+pub(crate) fn not_a_member(room_id: uuid::Uuid) -> rws_common::ErrorCode {
+    rws_common::ErrorCode::RoomNotFound {
+        message: format!("You are not a member of room {}", room_id),
+    }
+}
+
+fn user_not_in_room(user_id: uuid::Uuid, room_name: &str) -> rws_common::ErrorCode {
+    rws_common::ErrorCode::UserNotFound {
+        message: format!("User {} is not in {}", user_id, room_name),
+    }
+}
+
+fn user_info(clients: &Clients, user_id: uuid::Uuid) -> UserInfo {
+    UserInfo {
+        id: user_id,
+        username: get_username_from_client(clients, user_id).unwrap_or_else(|| "Unknown".to_string()),
+    }
+}
+
+/// Whether `client_id` negotiated a protocol with room moderation; see
+/// [`super::MODERATION_VERSION`].
+fn knows_moderation(clients: &Clients, client_id: uuid::Uuid) -> bool {
+    clients
+        .get(&client_id)
+        .and_then(|c| c.protocol_version)
+        .is_some_and(|v| v >= super::MODERATION_VERSION)
+}
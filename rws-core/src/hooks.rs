@@ -0,0 +1,118 @@
+use std::{future::Future, sync::Arc};
+
+use futures_util::future::BoxFuture;
+use rws_common::EventMessage;
+
+use crate::{client::Clients, config::ServerConfig, metrics::Metrics, room::SharedRoomManager};
+
+/// State handed to every hook invocation.
+#[derive(Clone)]
+pub struct HookContext {
+    pub sender_id: uuid::Uuid,
+    pub clients: Clients,
+    pub room_manager: SharedRoomManager,
+    pub config: Arc<ServerConfig>,
+    pub metrics: Arc<Metrics>,
+}
+
+/// An async callback registered on the server.
+pub type Hook<T = EventMessage> = Arc<dyn Fn(HookContext, T) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Wrap an async function or closure so it can be stored in [`EventHandlers`].
+pub fn hook<T, F, Fut>(f: F) -> Hook<T>
+where
+    F: Fn(HookContext, T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    Arc::new(move |ctx, arg| Box::pin(f(ctx, arg)))
+}
+
+/// Hooks invoked by the server for connection lifecycle changes and for
+/// every incoming event. Unset hooks are skipped.
+///
+/// Event hooks run after the server's own handling, and only if it accepted
+/// the event: a chat to a room the sender is not in, or a kick the sender may
+/// not make, is answered with `Error` and its hook is not called.
+#[derive(Clone, Default)]
+pub struct EventHandlers {
+    pub on_connect: Option<Hook<()>>,
+    pub on_disconnect: Option<Hook<()>>,
+    pub on_hello: Option<Hook>,
+    pub on_welcome: Option<Hook>,
+    pub on_join: Option<Hook>,
+    pub on_assigned_id: Option<Hook>,
+    pub on_chat: Option<Hook>,
+    pub on_ack_delivered: Option<Hook>,
+    pub on_ack_read: Option<Hook>,
+    pub on_create_room: Option<Hook>,
+    pub on_room_created: Option<Hook>,
+    pub on_list_rooms: Option<Hook>,
+    pub on_room_list: Option<Hook>,
+    pub on_get_room_members: Option<Hook>,
+    pub on_room_members: Option<Hook>,
+    pub on_set_room_role: Option<Hook>,
+    pub on_room_role_changed: Option<Hook>,
+    pub on_kick_member: Option<Hook>,
+    pub on_member_kicked: Option<Hook>,
+    pub on_ban_member: Option<Hook>,
+    pub on_member_banned: Option<Hook>,
+    pub on_join_room: Option<Hook>,
+    pub on_join_room_by_name: Option<Hook>,
+    pub on_leave_room: Option<Hook>,
+    pub on_change_username: Option<Hook>,
+    pub on_disconnected: Option<Hook>,
+    pub on_server_shutdown: Option<Hook>,
+    pub on_announcement: Option<Hook>,
+    pub on_room_deleted: Option<Hook>,
+    pub on_error: Option<Hook>,
+    pub on_ping: Option<Hook>,
+}
+
+impl EventHandlers {
+    /// Returns the hook registered for the variant of `message`, if any.
+    pub fn hook_for(&self, message: &EventMessage) -> Option<&Hook> {
+        match message {
+            EventMessage::Hello { .. } => self.on_hello.as_ref(),
+            EventMessage::Welcome { .. } => self.on_welcome.as_ref(),
+            EventMessage::Join { .. } => self.on_join.as_ref(),
+            EventMessage::AssignedId { .. } => self.on_assigned_id.as_ref(),
+            EventMessage::Chat { .. } => self.on_chat.as_ref(),
+            EventMessage::AckDelivered { .. } => self.on_ack_delivered.as_ref(),
+            EventMessage::AckRead { .. } => self.on_ack_read.as_ref(),
+            EventMessage::CreateRoom { .. } => self.on_create_room.as_ref(),
+            EventMessage::RoomCreated { .. } => self.on_room_created.as_ref(),
+            EventMessage::ListRooms { .. } => self.on_list_rooms.as_ref(),
+            EventMessage::RoomList { .. } => self.on_room_list.as_ref(),
+            EventMessage::GetRoomMembers { .. } => self.on_get_room_members.as_ref(),
+            EventMessage::RoomMembers { .. } => self.on_room_members.as_ref(),
+            EventMessage::SetRoomRole { .. } => self.on_set_room_role.as_ref(),
+            EventMessage::RoomRoleChanged { .. } => self.on_room_role_changed.as_ref(),
+            EventMessage::KickMember { .. } => self.on_kick_member.as_ref(),
+            EventMessage::MemberKicked { .. } => self.on_member_kicked.as_ref(),
+            EventMessage::BanMember { .. } => self.on_ban_member.as_ref(),
+            EventMessage::MemberBanned { .. } => self.on_member_banned.as_ref(),
+            EventMessage::JoinRoom { .. } => self.on_join_room.as_ref(),
+            EventMessage::JoinRoomByName { .. } => self.on_join_room_by_name.as_ref(),
+            EventMessage::LeaveRoom { .. } => self.on_leave_room.as_ref(),
+            EventMessage::ChangeUsername { .. } => self.on_change_username.as_ref(),
+            EventMessage::Disconnected { .. } => self.on_disconnected.as_ref(),
+            EventMessage::ServerShutdown { .. } => self.on_server_shutdown.as_ref(),
+            EventMessage::Announcement { .. } => self.on_announcement.as_ref(),
+            EventMessage::RoomDeleted { .. } => self.on_room_deleted.as_ref(),
+            EventMessage::Error { .. } => self.on_error.as_ref(),
+            EventMessage::Ping => self.on_ping.as_ref(),
+        }
+    }
+
+    pub async fn connected(&self, ctx: HookContext) {
+        if let Some(hook) = &self.on_connect {
+            hook(ctx, ()).await;
+        }
+    }
+
+    pub async fn disconnected(&self, ctx: HookContext) {
+        if let Some(hook) = &self.on_disconnect {
+            hook(ctx, ()).await;
+        }
+    }
+}
@@ -1,70 +1,294 @@
-use std::{collections::HashMap,sync::Arc};
-use tokio::{net::{TcpListener, ToSocketAddrs}, sync::Mutex};
-use futures_util::StreamExt;
-use tokio_tungstenite::accept_async;
-use rws_common::EventMessage;
+use std::{future::Future, net::SocketAddr, sync::Arc};
+use anyhow::anyhow;
+use tokio::{net::{TcpListener, ToSocketAddrs}, task::JoinSet};
 
-use crate::{client::{Client, Clients},  room::RoomManager};
+use crate::connection::Shared;
 
+pub use auth::{AuthError, Authenticator, HmacAuthenticator, Principal};
+pub use client::{Client, ClientRegistry, Clients};
+pub use config::{HeartbeatConfig, LimitsConfig, LoggingConfig, OutboundConfig, ServerConfig, ShutdownConfig, UsernameRules};
+pub use hooks::{hook, EventHandlers, Hook, HookContext};
+pub use metrics::Metrics;
+pub use outbound::{Outbound, SendError, SlowConsumerPolicy};
+pub use room::{Room, RoomManager, SharedRoomManager};
+pub use shutdown::{ShutdownHandle, ShutdownNotice};
+pub use tls::TlsAcceptor;
+pub use transport::{ListenAddr, Listener, MemoryConnector, MemoryListener};
 
-mod server;
-mod client;
-mod room;
+pub mod admin;
+pub mod auth;
+pub mod client;
+pub mod config;
+mod connection;
+pub mod dispatcher;
+pub mod handler;
+pub mod hooks;
+pub mod http;
+pub mod metrics;
+pub mod outbound;
+pub mod room;
+pub mod shutdown;
+pub mod tls;
+pub mod transport;
+pub mod util;
 
 pub struct Server {
-    addr: String,
+    listener: Box<dyn Listener>,
     clients: Clients,
-    room_manager: Arc<Mutex<RoomManager>>,
+    room_manager: SharedRoomManager,
+    handlers: Arc<EventHandlers>,
+    authenticator: Option<Arc<dyn Authenticator>>,
+    tls: Option<TlsAcceptor>,
+    config: Arc<ServerConfig>,
+    metrics: Arc<Metrics>,
+    metrics_listener: Option<TcpListener>,
+    admin: Option<(TcpListener, String)>,
+    shutdown: ShutdownHandle,
 }
 
 impl Server {
-    pub async fn bind(addr: impl ToSocketAddrs + ToString) -> anyhow::Result<Self> {
-        // test binding for early errors
-        TcpListener::bind(&addr).await?;
-
-        Ok(Self {
-            addr: addr.to_string(),
-            clients: Arc::new(Mutex::new(HashMap::new())),
-            room_manager: Arc::new(Mutex::new(RoomManager::new())),
-        })
+    /// Listen for TCP connections on `addr`.
+    pub async fn bind(addr: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        Ok(Self::with_listener(TcpListener::bind(addr).await?))
+    }
+
+    /// Listen on a Unix domain socket at `path`.
+    #[cfg(unix)]
+    pub fn bind_unix(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Ok(Self::with_listener(transport::bind_unix(path)?))
+    }
+
+    /// Serve in-process connections opened through the returned connector.
+    pub fn memory() -> (Self, MemoryConnector) {
+        let (listener, connector) = transport::memory();
+        (Self::with_listener(listener), connector)
+    }
+
+    /// Serve connections from any [`Listener`].
+    pub fn with_listener(listener: impl Listener) -> Self {
+        let metrics = Arc::new(Metrics::new());
+        Self {
+            listener: Box::new(listener),
+            clients: Arc::new(ClientRegistry::with_metrics(Arc::clone(&metrics))),
+            room_manager: Arc::new(RoomManager::new()),
+            handlers: Arc::new(EventHandlers::default()),
+            authenticator: None,
+            tls: None,
+            config: Arc::new(ServerConfig::default()),
+            metrics,
+            metrics_listener: None,
+            admin: None,
+            shutdown: ShutdownHandle::new(),
+        }
+    }
+
+    /// Register the hooks invoked for connection lifecycle and incoming events.
+    pub fn with_handlers(mut self, handlers: EventHandlers) -> Self {
+        self.handlers = Arc::new(handlers);
+        self
+    }
+
+    pub fn with_config(mut self, config: ServerConfig) -> Self {
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// Require every connection to authenticate during the WebSocket handshake.
+    /// Without an authenticator, connections are anonymous and named by `Join`.
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+
+    /// Terminate TLS on every accepted connection, serving `wss://`. See
+    /// [`tls::load_acceptor`] for building one from PEM files.
+    pub fn with_tls(mut self, acceptor: TlsAcceptor) -> Self {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    /// Serve Prometheus metrics at `GET /metrics` on a separate TCP address
+    /// while the server runs.
+    pub async fn with_metrics_endpoint(mut self, addr: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        self.metrics_listener = Some(TcpListener::bind(addr).await?);
+        Ok(self)
+    }
+
+    /// Address of the metrics endpoint, if one was configured.
+    pub fn metrics_addr(&self) -> Option<SocketAddr> {
+        self.metrics_listener.as_ref().and_then(|l| l.local_addr().ok())
+    }
+
+    /// Serve the [`admin`] API on a separate TCP address while the server runs.
+    /// Requests must present `token` as a bearer token.
+    pub async fn with_admin_endpoint(mut self, addr: impl ToSocketAddrs, token: impl Into<String>) -> anyhow::Result<Self> {
+        let token = token.into();
+        if token.is_empty() {
+            return Err(anyhow!("admin token must not be empty"));
+        }
+        self.admin = Some((TcpListener::bind(addr).await?, token));
+        Ok(self)
+    }
+
+    /// Address of the admin API, if one was configured.
+    pub fn admin_addr(&self) -> Option<SocketAddr> {
+        self.admin.as_ref().and_then(|(l, _)| l.local_addr().ok())
+    }
+
+    /// The TCP address the server is listening on, useful after binding port 0.
+    /// Fails for other listeners; see [`Server::listen_addr`].
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        match self.listener.local_addr()? {
+            ListenAddr::Tcp(addr) => Ok(addr),
+            other => Err(anyhow!("server is not listening on TCP ({})", other)),
+        }
+    }
+
+    /// Where the server accepts connections, whatever the transport.
+    pub fn listen_addr(&self) -> anyhow::Result<ListenAddr> {
+        Ok(self.listener.local_addr()?)
     }
 
+    /// Shared handle to the connected client registry.
+    pub fn clients(&self) -> Clients {
+        Arc::clone(&self.clients)
+    }
+
+    /// Shared handle to the room manager.
+    pub fn room_manager(&self) -> SharedRoomManager {
+        Arc::clone(&self.room_manager)
+    }
+
+    /// Outbound frames dropped across all clients by the slow-consumer policy.
+    pub fn dropped_frames(&self) -> u64 {
+        self.metrics.dropped_frames()
+    }
+
+    /// Shared handle to the server's metrics.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Handle for stopping [`Server::run`] gracefully from another task.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Serve connections until the shutdown handle is triggered, then notify
+    /// clients, close their connections and wait for queued frames to drain.
     pub async fn run(self) -> anyhow::Result<()> {
-        let listener = TcpListener::bind(&self.addr).await?;
-        println!("Starting RWS server on ws://{}...", self.addr);
-
-        while let Ok((stream, _)) = listener.accept().await {
-            let clients = Arc::clone(&self.clients);
-            let room_manager = Arc::clone(&self.room_manager);
-
-            tokio::spawn(async move {
-                let ws_stream = accept_async(stream).await.unwrap(); // WebSocket upgrade
-                let id = uuid::Uuid::new_v4();
-
-                println!("New client connected: {}", id);
-                let (write, mut read) = ws_stream.split();
-                let tx = Arc::new(Mutex::new(write));
-                let client = Client {
-                    id,
-                    username: None,
-                    tx: tx.clone(),
-                };
-
-                clients.lock().await.insert(id, client.clone());
-
-                while let Some(Ok(msg)) = read.next().await {
-                    if msg.is_text() {
-                        if let Ok(msg_obj) = serde_json::from_str::<EventMessage>(&msg.to_string()) {
-                            /* dispatch(msg_obj, id, &clients, &room_manager).await; */
-                        }
+        let scheme = if self.tls.is_some() { "wss" } else { "ws" };
+        tracing::info!(addr = %format_args!("{}://{}", scheme, self.listen_addr()?), "server listening");
+
+        let shared = Shared {
+            clients: Arc::clone(&self.clients),
+            room_manager: Arc::clone(&self.room_manager),
+            handlers: Arc::clone(&self.handlers),
+            authenticator: self.authenticator.clone(),
+            config: Arc::clone(&self.config),
+            metrics: Arc::clone(&self.metrics),
+            shutdown: self.shutdown.clone(),
+        };
+        let metrics_endpoint = self.metrics_listener.map(|listener| {
+            tracing::info!(addr = ?listener.local_addr().ok(), "serving metrics");
+            tokio::spawn(http::serve(listener, metrics_handler(&shared)))
+        });
+        let admin_endpoint = self.admin.map(|(listener, token)| {
+            tracing::info!(addr = ?listener.local_addr().ok(), "serving admin API");
+            let handler = admin::handler(Arc::clone(&shared.clients), Arc::clone(&shared.room_manager), token);
+            tokio::spawn(http::serve(listener, handler))
+        });
+        let mut shutdown = self.shutdown.subscribe();
+        let mut connections = JoinSet::new();
+
+        let mut listener = self.listener;
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(stream) => {
+                        connections.spawn(connection::accept(stream, self.tls.clone(), shared.clone()));
+                    }
+                    // Only a shutdown ends the loop; accept errors are usually transient
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to accept connection");
+                        self.metrics.record_accept_error();
+                        tokio::time::sleep(transport::ACCEPT_BACKOFF).await;
                     }
+                },
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                // `wait_for` also sees a notice triggered before `run` started
+                _ = async { shutdown.wait_for(Option::is_some).await.is_ok() } => break,
+            }
+        }
+
+        let notice = shutdown.borrow().clone().unwrap_or_default();
+        handler::handle_shutdown(&notice, &self.clients);
+
+        // Keep the listener open while draining so probes see /readyz fail;
+        // new WebSocket upgrades are refused
+        let mut probes = JoinSet::new();
+        let drained = tokio::time::timeout(self.config.shutdown.drain_timeout, async {
+            loop {
+                tokio::select! {
+                    joined = connections.join_next() => if joined.is_none() { break },
+                    accepted = listener.accept() => match accepted {
+                        Ok(stream) => {
+                            probes.spawn(connection::accept(stream, self.tls.clone(), shared.clone()));
+                        }
+                        Err(_) => tokio::time::sleep(transport::ACCEPT_BACKOFF).await,
+                    },
                 }
+            }
+        })
+        .await;
 
-                clients.lock().await.remove(&id);
-                println!("Client {} disconnected", id);
-            });
+        drop(listener);
+        probes.abort_all();
+
+        if drained.is_err() {
+            tracing::warn!(remaining = connections.len(), "shutdown drain timed out; dropping connections");
+            connections.abort_all();
+        }
+
+        for endpoint in [metrics_endpoint, admin_endpoint].into_iter().flatten() {
+            endpoint.abort();
         }
 
+        tracing::info!("server stopped");
         Ok(())
     }
+
+    /// Run until `signal` resolves, then shut down gracefully.
+    pub async fn run_until(self, signal: impl Future<Output = ()>) -> anyhow::Result<()> {
+        let handle = self.shutdown_handle();
+        let run = self.run();
+        tokio::pin!(run);
+
+        tokio::select! {
+            result = &mut run => return result,
+            _ = signal => handle.trigger(ShutdownNotice::default()),
+        }
+
+        run.await
+    }
+}
+
+/// `GET /metrics` in the Prometheus text format; everything else is 404.
+fn metrics_handler(shared: &Shared) -> http::Handler {
+    let clients = Arc::clone(&shared.clients);
+    let room_manager = Arc::clone(&shared.room_manager);
+    let metrics = Arc::clone(&shared.metrics);
+
+    Arc::new(move |request| {
+        let response = if request.method == "GET" && request.path == "/metrics" {
+            http::Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4",
+                body: metrics.render(clients.len(), room_manager.rooms.len()).into_bytes(),
+            }
+        } else {
+            http::Response::not_found()
+        };
+        Box::pin(async move { response })
+    })
 }
@@ -0,0 +1,202 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use rws_common::{ChatScope, ErrorCode};
+
+/// Upper bounds, in seconds, of the dispatch latency histogram buckets.
+const DISPATCH_BUCKETS: [f64; 12] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0,
+];
+
+/// Server-wide counters, shared by every connection and rendered in the
+/// Prometheus text format. Gauges that can be read directly from the client
+/// and room registries are computed at render time instead of tracked here.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    connections: AtomicU64,
+    accept_errors: AtomicU64,
+    global_messages: AtomicU64,
+    room_messages: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    frames_out: AtomicU64,
+    dropped_frames: AtomicU64,
+    encode_failures: AtomicU64,
+    dispatch: Histogram,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    handshake_failures: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_connection(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_accept_error(&self) {
+        self.accept_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_chat(&self, scope: &ChatScope) {
+        match scope {
+            ChatScope::Global => &self.global_messages,
+            ChatScope::Room { .. } => &self.room_messages,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.frames_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self) {
+        self.dropped_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self, error: &ErrorCode) {
+        *self.errors.lock().unwrap().entry(error.kind()).or_default() += 1;
+    }
+
+    pub fn record_encode_failure(&self) {
+        self.encode_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_handshake_failure(&self, kind: &'static str) {
+        *self.handshake_failures.lock().unwrap().entry(kind).or_default() += 1;
+    }
+
+    pub fn observe_dispatch(&self, elapsed: Duration) {
+        self.dispatch.observe(elapsed);
+    }
+
+    /// Outbound frames dropped across all clients by the slow-consumer policy.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+
+    /// Failed attempts to accept a connection from the listener.
+    pub fn accept_errors(&self) -> u64 {
+        self.accept_errors.load(Ordering::Relaxed)
+    }
+
+    /// Errors of the given kind (see [`ErrorCode::kind`]) sent to clients.
+    pub fn errors(&self, kind: &str) -> u64 {
+        self.errors.lock().unwrap().get(kind).copied().unwrap_or(0)
+    }
+
+    /// Events that could not be serialized and were never sent.
+    pub fn encode_failures(&self) -> u64 {
+        self.encode_failures.load(Ordering::Relaxed)
+    }
+
+    /// Connections of the given kind that failed before becoming a client.
+    pub fn handshake_failures(&self, kind: &str) -> u64 {
+        self.handshake_failures.lock().unwrap().get(kind).copied().unwrap_or(0)
+    }
+
+    /// Prometheus text exposition of every metric.
+    pub fn render(&self, connected_clients: usize, rooms: usize) -> String {
+        let mut out = String::new();
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+        gauge(&mut out, "rws_connected_clients", "Currently connected clients.", connected_clients as u64);
+        gauge(&mut out, "rws_rooms", "Rooms that currently exist.", rooms as u64);
+        counter(&mut out, "rws_connections_total", "WebSocket connections accepted.", load(&self.connections));
+        counter(&mut out, "rws_accept_errors_total", "Failed attempts to accept a connection.", load(&self.accept_errors));
+
+        header(&mut out, "rws_messages_total", "Chat messages handled, by scope.", "counter");
+        let _ = writeln!(out, "rws_messages_total{{scope=\"global\"}} {}", load(&self.global_messages));
+        let _ = writeln!(out, "rws_messages_total{{scope=\"room\"}} {}", load(&self.room_messages));
+
+        counter(&mut out, "rws_received_bytes_total", "Payload bytes received from clients.", load(&self.bytes_in));
+        counter(&mut out, "rws_sent_bytes_total", "Payload bytes written to clients.", load(&self.bytes_out));
+        counter(&mut out, "rws_sent_frames_total", "Frames written to clients, counting each broadcast recipient.", load(&self.frames_out));
+        counter(&mut out, "rws_dropped_frames_total", "Outbound frames dropped by the slow-consumer policy.", load(&self.dropped_frames));
+
+        header(&mut out, "rws_errors_total", "Protocol errors sent to clients, by code.", "counter");
+        for (kind, count) in self.errors.lock().unwrap().iter() {
+            let _ = writeln!(out, "rws_errors_total{{code=\"{}\"}} {}", kind, count);
+        }
+
+        header(&mut out, "rws_handshake_failures_total", "Connections dropped before completing the WebSocket handshake, by reason.", "counter");
+        for (kind, count) in self.handshake_failures.lock().unwrap().iter() {
+            let _ = writeln!(out, "rws_handshake_failures_total{{reason=\"{}\"}} {}", kind, count);
+        }
+        counter(&mut out, "rws_encode_failures_total", "Outbound events that failed to serialize and were not sent.", load(&self.encode_failures));
+
+        self.dispatch.render(&mut out, "rws_dispatch_duration_seconds", "Time spent handling one client event.");
+        out
+    }
+}
+
+#[derive(Debug)]
+struct Histogram {
+    buckets: [AtomicU64; DISPATCH_BUCKETS.len()],
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(i) = DISPATCH_BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "histogram");
+        let mut cumulative = 0;
+        for (bound, bucket) in DISPATCH_BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let sum = self.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "gauge");
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{} {}", name, value);
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use futures_util::{Sink, SinkExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame, Message as WsMessage};
+
+use crate::metrics::Metrics;
+
+/// What to do when a client's outbound queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumerPolicy {
+    /// Discard the oldest queued frame to make room.
+    #[default]
+    DropOldest,
+    /// Discard the frame being sent.
+    DropNewest,
+    /// Close the connection with a policy-violation close frame.
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// The connection is closing; nothing more will be written.
+    Closed,
+    /// The queue was full and a frame was dropped per the slow-consumer policy.
+    Dropped,
+}
+
+struct Inner {
+    queue: Mutex<VecDeque<WsMessage>>,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+    closed: AtomicBool,
+    ready: Notify,
+    closing: Notify,
+    dropped: AtomicU64,
+    metrics: Arc<Metrics>,
+}
+
+/// Bounded queue of frames waiting for a client's writer task.
+///
+/// Senders never wait on the socket: [`Outbound::push`] either enqueues the
+/// frame or applies the [`SlowConsumerPolicy`]. Cloning yields another handle
+/// to the same queue.
+#[derive(Clone)]
+pub struct Outbound {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for Outbound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Outbound")
+            .field("len", &self.len())
+            .field("capacity", &self.inner.capacity)
+            .field("policy", &self.inner.policy)
+            .field("closed", &self.is_closed())
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
+
+impl Outbound {
+    /// `metrics` is shared across clients so the server can report global
+    /// counts of sent and dropped frames.
+    pub fn new(capacity: usize, policy: SlowConsumerPolicy, metrics: Arc<Metrics>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                queue: Mutex::new(VecDeque::with_capacity(capacity)),
+                capacity: capacity.max(1),
+                policy,
+                closed: AtomicBool::new(false),
+                ready: Notify::new(),
+                closing: Notify::new(),
+                dropped: AtomicU64::new(0),
+                metrics,
+            }),
+        }
+    }
+
+    pub fn push(&self, message: WsMessage) -> Result<(), SendError> {
+        if self.is_closed() {
+            return Err(SendError::Closed);
+        }
+
+        let mut queue = self.inner.queue.lock().unwrap();
+        let result = if queue.len() < self.inner.capacity {
+            queue.push_back(message);
+            Ok(())
+        } else {
+            self.record_drop();
+            match self.inner.policy {
+                SlowConsumerPolicy::DropOldest => {
+                    queue.pop_front();
+                    queue.push_back(message);
+                }
+                SlowConsumerPolicy::DropNewest => {}
+                SlowConsumerPolicy::Disconnect => {
+                    queue.clear();
+                    drop(queue);
+                    self.close_with(CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: "slow consumer".into(),
+                    });
+                    return Err(SendError::Dropped);
+                }
+            }
+            Err(SendError::Dropped)
+        };
+        drop(queue);
+
+        self.inner.ready.notify_one();
+        result
+    }
+
+    /// Next frame for the writer task, or `None` once closed and drained.
+    pub async fn recv(&self) -> Option<WsMessage> {
+        loop {
+            let ready = self.inner.ready.notified();
+            {
+                let mut queue = self.inner.queue.lock().unwrap();
+                if let Some(message) = queue.pop_front() {
+                    return Some(message);
+                }
+                if self.is_closed() {
+                    return None;
+                }
+            }
+            ready.await;
+        }
+    }
+
+    /// Stop accepting frames. Already queued frames are still delivered.
+    pub fn close(&self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        self.inner.ready.notify_one();
+        self.inner.closing.notify_waiters();
+    }
+
+    /// Queue a close frame, bypassing the capacity limit, then close.
+    pub fn close_with(&self, frame: CloseFrame<'static>) {
+        if self.is_closed() {
+            return;
+        }
+        self.inner
+            .queue
+            .lock()
+            .unwrap()
+            .push_back(WsMessage::Close(Some(frame)));
+        self.close();
+    }
+
+    /// Resolves once the queue has been closed.
+    pub async fn closed(&self) {
+        loop {
+            let closing = self.inner.closing.notified();
+            if self.is_closed() {
+                return;
+            }
+            closing.await;
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::SeqCst)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.queue.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frames dropped for this client by the slow-consumer policy.
+    pub fn dropped(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Writer task body: forward queued frames to `sink` until the queue is
+    /// closed and drained or the socket fails.
+    pub async fn drain_into<S>(self, mut sink: S)
+    where
+        S: Sink<WsMessage> + Unpin,
+    {
+        while let Some(message) = self.recv().await {
+            let len = message.len();
+            if sink.send(message).await.is_err() {
+                self.close();
+                return;
+            }
+            self.inner.metrics.record_sent(len);
+        }
+        let _ = sink.close().await;
+    }
+
+    fn record_drop(&self) {
+        self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+        self.inner.metrics.record_dropped();
+    }
+
+    pub(crate) fn metrics(&self) -> &Metrics {
+        &self.inner.metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(n: u32) -> WsMessage {
+        WsMessage::Text(n.to_string())
+    }
+
+    /// Push frames 1..=4 into a queue holding two.
+    fn overfilled(policy: SlowConsumerPolicy) -> (Outbound, Arc<Metrics>, Vec<Result<(), SendError>>) {
+        let metrics = Arc::new(Metrics::new());
+        let tx = Outbound::new(2, policy, Arc::clone(&metrics));
+        let results = (1..=4).map(|n| tx.push(text(n))).collect();
+        (tx, metrics, results)
+    }
+
+    async fn drain(tx: &Outbound) -> Vec<WsMessage> {
+        tx.close();
+        let mut frames = Vec::new();
+        while let Some(frame) = tx.recv().await {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_newest_frames() {
+        let (tx, metrics, results) = overfilled(SlowConsumerPolicy::DropOldest);
+        assert_eq!(results, [Ok(()), Ok(()), Err(SendError::Dropped), Err(SendError::Dropped)]);
+        assert_eq!(drain(&tx).await, [text(3), text(4)]);
+        assert_eq!(tx.dropped(), 2);
+        assert_eq!(metrics.dropped_frames(), 2);
+    }
+
+    #[tokio::test]
+    async fn drop_newest_keeps_the_oldest_frames() {
+        let (tx, metrics, results) = overfilled(SlowConsumerPolicy::DropNewest);
+        assert_eq!(results, [Ok(()), Ok(()), Err(SendError::Dropped), Err(SendError::Dropped)]);
+        assert_eq!(drain(&tx).await, [text(1), text(2)]);
+        assert_eq!(tx.dropped(), 2);
+        assert_eq!(metrics.dropped_frames(), 2);
+    }
+
+    #[tokio::test]
+    async fn disconnect_closes_the_queue_with_a_policy_frame() {
+        let (tx, metrics, results) = overfilled(SlowConsumerPolicy::Disconnect);
+        assert_eq!(results, [Ok(()), Ok(()), Err(SendError::Dropped), Err(SendError::Closed)]);
+        assert!(tx.is_closed());
+        assert_eq!(tx.dropped(), 1);
+        assert_eq!(metrics.dropped_frames(), 1);
+
+        let frames = drain(&tx).await;
+        assert!(matches!(&frames[..], [WsMessage::Close(Some(frame))] if frame.code == CloseCode::Policy));
+    }
+}
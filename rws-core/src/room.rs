@@ -1,37 +1,269 @@
-use std::{collections::{HashMap, HashSet}, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Instant,
+};
 
-use tokio::sync::Mutex;
+use dashmap::{mapref::entry::Entry, DashMap};
+use rws_common::{RoomRole, RoomVisibility};
+
+use crate::client::Client;
 
 
 #[derive(Debug, Clone)]
 pub struct Room{
     pub id: uuid::Uuid,
     pub name: String,
+    /// Unique normalized name; see [`rws_common::room_slug`].
+    pub slug: String,
     pub owner_id : uuid::Uuid,
     pub members : HashSet<uuid::Uuid>,
+    /// When each member joined, to pick the next owner.
+    pub joined: HashMap<uuid::Uuid, Instant>,
+    pub moderators: HashSet<uuid::Uuid>,
+    pub bans: Vec<Ban>,
+    pub topic: Option<String>,
+    pub visibility: RoomVisibility,
+}
+
+impl Room {
+    /// A room whose only member is its owner.
+    pub fn new(id: uuid::Uuid, name: String, slug: String, owner_id: uuid::Uuid) -> Self {
+        Self {
+            id,
+            name,
+            slug,
+            owner_id,
+            members: HashSet::from([owner_id]),
+            joined: HashMap::from([(owner_id, Instant::now())]),
+            moderators: HashSet::new(),
+            bans: Vec::new(),
+            topic: None,
+            visibility: RoomVisibility::default(),
+        }
+    }
+
+    pub fn role(&self, user_id: &uuid::Uuid) -> Option<RoomRole> {
+        if !self.members.contains(user_id) {
+            None
+        } else if self.owner_id == *user_id {
+            Some(RoomRole::Owner)
+        } else if self.moderators.contains(user_id) {
+            Some(RoomRole::Moderator)
+        } else {
+            Some(RoomRole::Member)
+        }
+    }
+
+    /// Whether `actor` may kick or ban `target`: owners may act on anyone
+    /// else, moderators on plain members and on users outside the room.
+    pub fn can_moderate(&self, actor: &uuid::Uuid, target: &uuid::Uuid) -> bool {
+        actor != target
+            && match self.role(actor) {
+                Some(RoomRole::Owner) => true,
+                Some(RoomRole::Moderator) => self.role(target).is_none_or(|role| role == RoomRole::Member),
+                _ => false,
+            }
+    }
+
+    pub fn add_member(&mut self, user_id: uuid::Uuid) {
+        if self.members.insert(user_id) {
+            self.joined.insert(user_id, Instant::now());
+        }
+    }
+
+    /// Remove `user_id`, handing the room to the longest-standing moderator,
+    /// or failing that member, if they owned it. Returns the new owner.
+    pub fn remove_member(&mut self, user_id: &uuid::Uuid) -> Option<uuid::Uuid> {
+        self.members.remove(user_id);
+        self.joined.remove(user_id);
+        self.moderators.remove(user_id);
+        if self.owner_id != *user_id {
+            return None;
+        }
+
+        let successor = self
+            .joined
+            .iter()
+            .min_by_key(|(id, since)| (!self.moderators.contains(id), **since))
+            .map(|(id, _)| *id)?;
+        self.moderators.remove(&successor);
+        self.owner_id = successor;
+        Some(successor)
+    }
+
+    /// The ban keeping `client` out, if any. Expired bans are dropped.
+    pub fn ban_for(&mut self, client: &Client) -> Option<Ban> {
+        let now = Instant::now();
+        self.bans.retain(|ban| ban.until.is_none_or(|until| until > now));
+        self.bans.iter().find(|ban| ban.applies_to(client)).cloned()
+    }
+}
+
+/// Keeps someone out of a room. Matched by connection id, by username, and
+/// for authenticated users by token subject, so an anonymous user can only
+/// get back in by reconnecting under another name.
+#[derive(Debug, Clone)]
+pub struct Ban {
+    pub user_id: uuid::Uuid,
+    /// Lowercased.
+    pub username: Option<String>,
+    pub subject: Option<String>,
+    pub reason: Option<String>,
+    /// `None` for a ban lasting as long as the room.
+    pub until: Option<Instant>,
+}
+
+impl Ban {
+    pub fn new(client: &Client, reason: Option<String>, until: Option<Instant>) -> Self {
+        Self {
+            user_id: client.id,
+            username: client.username.as_ref().map(|name| name.to_lowercase()),
+            subject: client.principal.as_ref().map(|p| p.subject.clone()),
+            reason,
+            until,
+        }
+    }
+
+    pub fn applies_to(&self, client: &Client) -> bool {
+        self.user_id == client.id
+            || self.username.is_some() && self.username == client.username.as_ref().map(|name| name.to_lowercase())
+            || self.subject.is_some() && self.subject == client.principal.as_ref().map(|p| p.subject.clone())
+    }
+}
+
+/// Position in the name-ordered room list; see [`RoomManager::list_rooms`].
+/// Encoded as an opaque string for clients.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RoomCursor {
+    name: String,
+    id: uuid::Uuid,
 }
 
-#[derive(Debug, Clone, Default)]
+impl RoomCursor {
+    fn of(room: &Room) -> Self {
+        Self {
+            name: room.name.to_lowercase(),
+            id: room.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.id, self.name)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let (id, name) = cursor.split_once(':')?;
+        Some(Self {
+            name: name.to_string(),
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+/// Rooms and memberships, held in sharded maps so that traffic in unrelated
+/// rooms never contends on a single lock.
+#[derive(Debug, Default)]
 pub struct RoomManager{
-    pub rooms : HashMap<uuid::Uuid, Room>,
-    pub user_rooms : HashMap<uuid::Uuid, uuid::Uuid>, //user -> room
+    pub rooms : DashMap<uuid::Uuid, Room>,
+    pub user_rooms : DashMap<uuid::Uuid, Vec<uuid::Uuid>>, //user -> rooms, in join order
+    pub slugs: DashMap<String, uuid::Uuid>, //slug -> room
 }
 
-pub type SharedRoomManager = Arc<Mutex<RoomManager>>;
+pub type SharedRoomManager = Arc<RoomManager>;
 
 impl RoomManager {
     pub fn new() -> Self {
-        RoomManager {
-            rooms: HashMap::new(),
-            user_rooms: HashMap::new(),
-        }
+        Self::default()
     }
 
     pub fn get_room(&self, room_id: &uuid::Uuid) -> Option<Room> {
-        self.rooms.get(room_id).cloned()
+        self.rooms.get(room_id).map(|r| r.value().clone())
+    }
+
+    /// Up to `limit` rooms after `after`, ordered by name, that `viewer` may
+    /// see and whose name or topic contains `filter` ignoring case. Also
+    /// returns where the next page starts, if there is one.
+    pub fn list_rooms(
+        &self,
+        viewer: &uuid::Uuid,
+        filter: Option<&str>,
+        after: Option<&RoomCursor>,
+        limit: usize,
+    ) -> (Vec<Room>, Option<RoomCursor>) {
+        let filter = filter.map(str::to_lowercase).filter(|f| !f.is_empty());
+        let matches = |room: &Room| match &filter {
+            Some(filter) => {
+                room.name.to_lowercase().contains(filter)
+                    || room.topic.as_ref().is_some_and(|t| t.to_lowercase().contains(filter))
+            }
+            None => true,
+        };
+
+        let mut rooms: Vec<(RoomCursor, Room)> = self
+            .rooms
+            .iter()
+            .map(|r| r.value().clone())
+            .filter(|room| room.visibility == RoomVisibility::Public || room.members.contains(viewer))
+            .filter(|room| matches(room))
+            .map(|room| (RoomCursor::of(&room), room))
+            .filter(|(key, _)| after.is_none_or(|after| key > after))
+            .collect();
+        rooms.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let next = (limit > 0 && rooms.len() > limit).then(|| rooms[limit - 1].0.clone());
+        rooms.truncate(limit);
+        (rooms.into_iter().map(|(_, room)| room).collect(), next)
+    }
+
+    /// The room whose name normalizes the same as `name`.
+    pub fn find_by_name(&self, name: &str) -> Option<uuid::Uuid> {
+        let slug = rws_common::room_slug(name)?;
+        self.slugs.get(&slug).map(|r| *r.value())
     }
 
-    pub fn get_user_room(&self, user_id: &uuid::Uuid) -> Option<uuid::Uuid> {
-        self.user_rooms.get(user_id).cloned()
+    /// Remove a room and free its name.
+    pub(crate) fn remove_room(&self, room_id: &uuid::Uuid) -> Option<Room> {
+        self.remove_room_if(room_id, |_| true)
     }
-}
\ No newline at end of file
+
+    /// Remove a room and free its name if `pred` holds, checked atomically.
+    pub(crate) fn remove_room_if(&self, room_id: &uuid::Uuid, pred: impl FnOnce(&Room) -> bool) -> Option<Room> {
+        let (_, room) = self.rooms.remove_if(room_id, |_, room| pred(room))?;
+        self.slugs.remove_if(&room.slug, |_, id| id == room_id);
+        Some(room)
+    }
+
+    /// Rooms `user_id` belongs to, oldest membership first.
+    pub fn get_user_rooms(&self, user_id: &uuid::Uuid) -> Vec<uuid::Uuid> {
+        self.user_rooms.get(user_id).map(|r| r.value().clone()).unwrap_or_default()
+    }
+
+    pub fn is_member(&self, room_id: &uuid::Uuid, user_id: &uuid::Uuid) -> bool {
+        self.rooms.get(room_id).is_some_and(|r| r.members.contains(user_id))
+    }
+
+    pub(crate) fn add_membership(&self, user_id: uuid::Uuid, room_id: uuid::Uuid) {
+        let mut rooms = self.user_rooms.entry(user_id).or_default();
+        if !rooms.contains(&room_id) {
+            rooms.push(room_id);
+        }
+    }
+
+    pub(crate) fn remove_membership(&self, user_id: &uuid::Uuid, room_id: &uuid::Uuid) {
+        if let Entry::Occupied(mut entry) = self.user_rooms.entry(*user_id) {
+            entry.get_mut().retain(|joined| joined != room_id);
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Snapshot of a room's member ids.
+    pub fn members(&self, room_id: &uuid::Uuid) -> Vec<uuid::Uuid> {
+        self.rooms
+            .get(room_id)
+            .map(|r| r.members.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,53 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+
+/// Details broadcast to clients when the server begins shutting down.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownNotice {
+    /// Suggested delay before clients try to reconnect.
+    pub reconnect_after: Option<Duration>,
+}
+
+/// Cloneable handle that asks a running [`crate::Server`] to shut down
+/// gracefully. Obtain it with [`crate::Server::shutdown_handle`] before `run`.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    tx: Arc<watch::Sender<Option<ShutdownNotice>>>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> Self {
+        let (tx, _) = watch::channel(None);
+        Self { tx: Arc::new(tx) }
+    }
+
+    /// Refuse new WebSocket connections, notify clients and drain their queues.
+    /// Only the first call has an effect.
+    pub fn trigger(&self, notice: ShutdownNotice) {
+        self.tx.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(notice);
+            true
+        });
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.tx.borrow().is_some()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<Option<ShutdownNotice>> {
+        self.tx.subscribe()
+    }
+}
+
+/// Close frame sent to every client when the server goes away.
+pub(crate) fn close_frame() -> CloseFrame<'static> {
+    CloseFrame {
+        code: CloseCode::Away,
+        reason: "server shutting down".into(),
+    }
+}
@@ -0,0 +1,50 @@
+use std::{fs, io::BufReader, path::Path, sync::Arc};
+
+use anyhow::{anyhow, Context};
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+
+pub use tokio_rustls::{rustls, TlsAcceptor};
+
+/// Build a TLS acceptor from a PEM certificate chain and private key on disk.
+pub fn load_acceptor(cert_path: impl AsRef<Path>, key_path: impl AsRef<Path>) -> anyhow::Result<TlsAcceptor> {
+    let cert_path = cert_path.as_ref();
+    let key_path = key_path.as_ref();
+    let cert_pem = fs::read(cert_path).with_context(|| format!("failed to read certificate {}", cert_path.display()))?;
+    let key_pem = fs::read(key_path).with_context(|| format!("failed to read private key {}", key_path.display()))?;
+    acceptor_from_pem(&cert_pem, &key_pem)
+}
+
+/// Build a TLS acceptor from in-memory PEM data. The key may be PKCS#8, PKCS#1
+/// (RSA) or SEC1 (EC).
+pub fn acceptor_from_pem(cert_pem: &[u8], key_pem: &[u8]) -> anyhow::Result<TlsAcceptor> {
+    let certs: Vec<Certificate> = rustls_pemfile::certs(&mut BufReader::new(cert_pem))
+        .context("invalid certificate PEM")?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates found in PEM data"));
+    }
+
+    let key = private_key(key_pem)?;
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("certificate and private key do not match")?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn private_key(key_pem: &[u8]) -> anyhow::Result<PrivateKey> {
+    let mut reader = BufReader::new(key_pem);
+    loop {
+        match rustls_pemfile::read_one(&mut reader).context("invalid private key PEM")? {
+            Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => return Ok(PrivateKey(key)),
+            Some(_) => continue,
+            None => return Err(anyhow!("no private key found in PEM data")),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::{fmt, io, net::SocketAddr, time::Duration};
+
+use futures_util::{future::BoxFuture, FutureExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, DuplexStream},
+    net::TcpListener,
+    sync::mpsc,
+};
+
+/// Any byte stream a WebSocket can run over.
+pub trait Transport: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Transport for T {}
+
+pub type BoxedStream = Box<dyn Transport>;
+
+/// Pause after a failed accept before trying again. Failures such as running
+/// out of file descriptors usually clear up, but retrying at once would spin.
+pub(crate) const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Source of incoming connections for a [`crate::Server`].
+///
+/// `accept` must be cancel-safe: the server polls it alongside shutdown and
+/// drops the future when another branch wins.
+pub trait Listener: Send + 'static {
+    fn accept(&mut self) -> BoxFuture<'_, io::Result<BoxedStream>>;
+
+    fn local_addr(&self) -> io::Result<ListenAddr>;
+}
+
+/// Where a [`Listener`] accepts connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+    Memory,
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+            ListenAddr::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+impl Listener for TcpListener {
+    fn accept(&mut self) -> BoxFuture<'_, io::Result<BoxedStream>> {
+        async move {
+            let (stream, _) = TcpListener::accept(self).await?;
+            Ok(Box::new(stream) as BoxedStream)
+        }
+        .boxed()
+    }
+
+    fn local_addr(&self) -> io::Result<ListenAddr> {
+        TcpListener::local_addr(self).map(ListenAddr::Tcp)
+    }
+}
+
+#[cfg(unix)]
+pub use unix::bind_unix;
+
+#[cfg(unix)]
+mod unix {
+    use std::{io, path::Path};
+
+    use futures_util::{future::BoxFuture, FutureExt};
+    use tokio::net::UnixListener;
+
+    use super::{BoxedStream, ListenAddr, Listener};
+
+    /// Bind a Unix domain socket, replacing a stale socket file left behind by
+    /// a previous process. A socket that still accepts connections is left
+    /// alone and reported as in use.
+    pub fn bind_unix(path: impl AsRef<Path>) -> io::Result<UnixListener> {
+        let path = path.as_ref();
+        if path.exists() {
+            match std::os::unix::net::UnixStream::connect(path) {
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        format!("{} is already being served", path.display()),
+                    ));
+                }
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => std::fs::remove_file(path)?,
+                Err(e) => return Err(e),
+            }
+        }
+        UnixListener::bind(path)
+    }
+
+    impl Listener for UnixListener {
+        fn accept(&mut self) -> BoxFuture<'_, io::Result<BoxedStream>> {
+            async move {
+                let (stream, _) = UnixListener::accept(self).await?;
+                Ok(Box::new(stream) as BoxedStream)
+            }
+            .boxed()
+        }
+
+        fn local_addr(&self) -> io::Result<ListenAddr> {
+            let addr = UnixListener::local_addr(self)?;
+            let path = addr
+                .as_pathname()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "unnamed unix socket"))?;
+            Ok(ListenAddr::Unix(path.to_path_buf()))
+        }
+    }
+}
+
+/// Buffer size of each in-memory pipe direction.
+const MEMORY_PIPE_CAPACITY: usize = 64 * 1024;
+
+/// In-process listener fed by a [`MemoryConnector`]. Connections are
+/// `tokio::io::duplex` pipes, so no sockets or ports are involved.
+#[derive(Debug)]
+pub struct MemoryListener {
+    incoming: mpsc::UnboundedReceiver<DuplexStream>,
+}
+
+/// Opens connections to a [`MemoryListener`]. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct MemoryConnector {
+    tx: mpsc::UnboundedSender<DuplexStream>,
+}
+
+/// Create a connected in-memory listener and connector pair.
+pub fn memory() -> (MemoryListener, MemoryConnector) {
+    let (tx, incoming) = mpsc::unbounded_channel();
+    (MemoryListener { incoming }, MemoryConnector { tx })
+}
+
+impl MemoryConnector {
+    /// Open a new connection. Fails once the listener has been dropped.
+    pub fn connect(&self) -> io::Result<DuplexStream> {
+        let (client, server) = tokio::io::duplex(MEMORY_PIPE_CAPACITY);
+        self.tx
+            .send(server)
+            .map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, "memory listener closed"))?;
+        Ok(client)
+    }
+}
+
+impl Listener for MemoryListener {
+    fn accept(&mut self) -> BoxFuture<'_, io::Result<BoxedStream>> {
+        async move {
+            match self.incoming.recv().await {
+                Some(stream) => Ok(Box::new(stream) as BoxedStream),
+                // Every connector is gone; nothing more will arrive
+                None => std::future::pending().await,
+            }
+        }
+        .boxed()
+    }
+
+    fn local_addr(&self) -> io::Result<ListenAddr> {
+        Ok(ListenAddr::Memory)
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+
+use rws_common::EventMessage;
+use tokio_tungstenite::tungstenite::protocol::Message as WsMessage;
+
+use crate::{client::Clients, metrics::Metrics, room::RoomManager};
+
+/// An event that could not be serialized to JSON.
+#[derive(Debug)]
+pub struct EncodeError(serde_json::Error);
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to encode event: {}", self.0)
+    }
+}
+
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Serialize `event` into the JSON text sent over the wire.
+pub fn encode(event: &EventMessage) -> Result<String, EncodeError> {
+    serde_json::to_string(event).map_err(EncodeError)
+}
+
+/// Encode `event` for the helpers below, which have nobody to return an error
+/// to: failures are logged and counted, and the event is not sent.
+fn encode_or_log(event: &EventMessage, metrics: &Metrics) -> Option<String> {
+    match encode(event) {
+        Ok(payload) => Some(payload),
+        Err(e) => {
+            tracing::error!(error = %e, "dropping event");
+            metrics.record_encode_failure();
+            None
+        }
+    }
+}
+
+/// Broadcast a message to all connected clients
+pub fn send(message: &EventMessage, clients: &Clients) {
+    let Some(payload) = encode_or_log(message, clients.metrics()) else { return };
+
+    clients.for_each(|client| {
+        let _ = client.tx.push(WsMessage::Text(payload.clone()));
+    });
+}
+
+/// Broadcast a message to all connected clients except the sender
+pub fn broadcast(message: &EventMessage, sender_id: uuid::Uuid, clients: &Clients) {
+    let Some(payload) = encode_or_log(message, clients.metrics()) else { return };
+
+    clients.for_each(|client| {
+        if client.id != sender_id {
+            let _ = client.tx.push(WsMessage::Text(payload.clone()));
+        }
+    });
+}
+
+pub fn broadcast_to_room(
+    message: &EventMessage,
+    room_id: uuid::Uuid,
+    rm: &RoomManager,
+    clients: &Clients,
+){
+    let Some(payload) = encode_or_log(message, clients.metrics()) else { return };
+
+    for id in rm.members(&room_id) {
+        if let Some(client) = clients.get(&id) {
+            let _ = client.tx.push(WsMessage::Text(payload.clone()));
+        }
+    }
+}
+
+/// Get a client by ID
+pub fn get_client_by_id(
+    clients: &Clients,
+    client_id: uuid::Uuid,
+) -> Option<crate::client::Client> {
+    clients.get(&client_id)
+}
+
+/// Queue a message for a specific client instance. Errors are counted in the
+/// server metrics by code.
+pub fn send_to_client_instance(
+    client: &crate::client::Client,
+    event: EventMessage,
+) {
+    if let EventMessage::Error { error } = &event {
+        client.tx.metrics().record_error(error);
+    }
+    if let Some(payload) = encode_or_log(&event, client.tx.metrics()) {
+        let _ = client.tx.push(WsMessage::Text(payload));
+    }
+}
+
+/// Send a message to a specific client by ID
+pub fn send_to_client(
+    clients: &Clients,
+    client_id: uuid::Uuid,
+    event: EventMessage,
+) {
+    if let Some(client) = get_client_by_id(clients, client_id) {
+        send_to_client_instance(&client, event);
+    }
+}
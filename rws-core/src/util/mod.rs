@@ -0,0 +1,10 @@
+use crate::client::Clients;
+
+pub mod broadcast;
+
+pub fn get_username_from_client(
+    clients: &Clients,
+    client_id: uuid::Uuid,
+) -> Option<String> {
+    clients.username(&client_id)
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{http, join, recv_until, send, spawn};
+use futures_util::StreamExt;
+use rws_common::EventMessage;
+use rws_core::Server;
+use tokio_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message as WsMessage};
+
+const TOKEN: &str = "s3cret";
+
+#[tokio::test]
+async fn admin_api_requires_the_token() {
+    let (server, _connector) = Server::memory();
+    let server = server.with_admin_endpoint("127.0.0.1:0", TOKEN).await.unwrap();
+    let admin = server.admin_addr().unwrap();
+    spawn(server);
+
+    assert_eq!(http(admin, "GET", "/clients", None, "").await.0, 401);
+    assert_eq!(http(admin, "GET", "/clients", Some("wrong"), "").await.0, 401);
+    assert_eq!(http(admin, "GET", "/clients", Some(TOKEN), "").await.0, 200);
+}
+
+#[tokio::test]
+async fn operators_can_inspect_kick_delete_and_announce() {
+    let (server, connector) = Server::memory();
+    let server = server.with_admin_endpoint("127.0.0.1:0", TOKEN).await.unwrap();
+    let admin = server.admin_addr().unwrap();
+    let room_manager = server.room_manager();
+    spawn(server);
+
+    let (mut alice, _) = join(&connector, "alice").await;
+    let (mut bob, bob_id) = join(&connector, "bob").await;
+
+    send(&mut alice, &EventMessage::CreateRoom { creator: None, room_name: "lobby".into(), topic: None, visibility: Default::default() }).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::RoomCreated { .. })).await;
+    let room_id = *room_manager.rooms.iter().next().unwrap().key();
+
+    let (status, body) = http(admin, "GET", "/clients", Some(TOKEN), "").await;
+    assert_eq!(status, 200);
+    let clients: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(clients.as_array().unwrap().len(), 2);
+
+    let (status, body) = http(admin, "GET", &format!("/rooms/{}", room_id), Some(TOKEN), "").await;
+    assert_eq!(status, 200);
+    let room: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(room["name"], "lobby");
+    assert_eq!(room["members"][0]["username"], "alice");
+
+    let announcement = r#"{"message":"maintenance at noon"}"#;
+    assert_eq!(http(admin, "POST", "/announcements", Some(TOKEN), announcement).await.0, 204);
+    let received = recv_until(&mut bob, |e| matches!(e, EventMessage::Announcement { .. })).await;
+    assert!(matches!(received, EventMessage::Announcement { message } if message == "maintenance at noon"));
+
+    assert_eq!(http(admin, "DELETE", &format!("/rooms/{}", room_id), Some(TOKEN), "").await.0, 204);
+    recv_until(&mut alice, |e| matches!(e, EventMessage::RoomDeleted { .. })).await;
+    assert!(room_manager.rooms.is_empty() && room_manager.user_rooms.is_empty());
+    assert_eq!(http(admin, "DELETE", &format!("/rooms/{}", room_id), Some(TOKEN), "").await.0, 404);
+
+    // Close frames carry at most 123 bytes of reason
+    let long = format!(r#"{{"reason":"{}"}}"#, "é".repeat(62));
+    assert_eq!(http(admin, "POST", &format!("/clients/{}/kick", bob_id), Some(TOKEN), &long).await.0, 400);
+
+    let kick = r#"{"reason":"spam"}"#;
+    assert_eq!(http(admin, "POST", &format!("/clients/{}/kick", bob_id), Some(TOKEN), kick).await.0, 204);
+    loop {
+        match bob.next().await {
+            Some(Ok(WsMessage::Close(Some(frame)))) => {
+                assert_eq!(frame.code, CloseCode::Policy);
+                assert_eq!(frame.reason, "spam");
+                break;
+            }
+            Some(Ok(_)) => continue,
+            other => panic!("expected close frame, got {:?}", other),
+        }
+    }
+    let gone = recv_until(&mut alice, |e| matches!(e, EventMessage::Disconnected { .. })).await;
+    assert!(matches!(gone, EventMessage::Disconnected { user } if user.id == bob_id));
+}
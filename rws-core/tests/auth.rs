@@ -0,0 +1,65 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{recv_until, send, spawn, Ws};
+use rws_common::{ChatScope, ErrorCode, EventMessage};
+use rws_core::{HmacAuthenticator, MemoryConnector, Server};
+use tokio_tungstenite::{
+    client_async,
+    tungstenite::{client::IntoClientRequest, http::header, Error as WsError},
+};
+
+const KEY: &str = "test-secret";
+
+async fn connect_with(connector: &MemoryConnector, token: Option<&str>) -> Result<Ws, WsError> {
+    let mut request = "ws://memory/".into_client_request().unwrap();
+    if let Some(token) = token {
+        request.headers_mut().insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+    }
+    client_async(request, connector.connect().unwrap()).await.map(|(ws, _)| ws)
+}
+
+#[tokio::test]
+async fn upgrades_without_a_valid_token_are_refused() {
+    let (server, connector) = Server::memory();
+    spawn(server.with_authenticator(HmacAuthenticator::new(KEY)));
+
+    for token in [None, Some("not.a-token")] {
+        match connect_with(&connector, token).await {
+            Err(WsError::Http(response)) => {
+                assert_eq!(response.status(), 401);
+                assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+            }
+            other => panic!("expected 401, got {:?}", other.map(|_| ())),
+        }
+    }
+}
+
+#[tokio::test]
+async fn authenticated_clients_are_named_by_their_token() {
+    let auth = HmacAuthenticator::new(KEY);
+    let (server, connector) = Server::memory();
+    spawn(server.with_authenticator(auth.clone()));
+
+    let token = auth.issue("user-1", "alice", Duration::from_secs(60));
+    let mut alice = connect_with(&connector, Some(&token)).await.unwrap();
+
+    // No Join needed to chat under the token's name
+    let chat = EventMessage::Chat { id: uuid::Uuid::new_v4(), sender: None, content: "hi".into(), scope: ChatScope::Global };
+    send(&mut alice, &chat).await;
+    let echoed = recv_until(&mut alice, |e| matches!(e, EventMessage::Chat { .. })).await;
+    assert!(matches!(echoed, EventMessage::Chat { sender: Some(s), .. } if s.username == "alice"));
+
+    // Join only acknowledges; the requested name is ignored
+    send(&mut alice, &EventMessage::Join { username: "mallory".into() }).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::AssignedId { .. })).await;
+    send(&mut alice, &chat).await;
+    let echoed = recv_until(&mut alice, |e| matches!(e, EventMessage::Chat { .. })).await;
+    assert!(matches!(echoed, EventMessage::Chat { sender: Some(s), .. } if s.username == "alice"));
+
+    // A second connection for the same name is turned away
+    let mut again = connect_with(&connector, Some(&token)).await.unwrap();
+    let error = recv_until(&mut again, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::UsernameTaken { .. } }));
+}
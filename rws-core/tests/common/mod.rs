@@ -0,0 +1,109 @@
+#![allow(dead_code)]
+
+use std::{future::Future, net::SocketAddr, time::Duration};
+
+use futures_util::{SinkExt, StreamExt};
+use rws_common::EventMessage;
+use rws_core::{MemoryConnector, Server};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream},
+    net::TcpStream,
+};
+use tokio_tungstenite::{client_async, tungstenite::Message as WsMessage, WebSocketStream};
+
+pub type Ws = WebSocketStream<DuplexStream>;
+
+/// Run `server` in the background.
+pub fn spawn(server: Server) {
+    tokio::spawn(server.run());
+}
+
+pub async fn connect(connector: &MemoryConnector) -> Ws {
+    let stream = connector.connect().unwrap();
+    let (ws, _) = client_async("ws://memory/", stream).await.unwrap();
+    ws
+}
+
+pub async fn send<S>(ws: &mut WebSocketStream<S>, event: &EventMessage)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    ws.send(WsMessage::Text(serde_json::to_string(event).unwrap()))
+        .await
+        .unwrap();
+}
+
+/// Read events until one matches `pred`, failing after a few seconds.
+pub async fn recv_until<S>(ws: &mut WebSocketStream<S>, pred: impl Fn(&EventMessage) -> bool) -> EventMessage
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(msg) = ws.next().await {
+            if let Ok(WsMessage::Text(text)) = msg
+                && let Ok(event) = serde_json::from_str::<EventMessage>(&text)
+                && pred(&event)
+            {
+                return event;
+            }
+        }
+        panic!("connection closed while waiting for event");
+    })
+    .await
+    .expect("timed out waiting for event")
+}
+
+/// `Join` as `username` over an open socket, returning the assigned id.
+pub async fn join_with<S>(ws: &mut WebSocketStream<S>, username: &str) -> uuid::Uuid
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    send(ws, &EventMessage::Join { username: username.to_string() }).await;
+    match recv_until(ws, |e| matches!(e, EventMessage::AssignedId { .. })).await {
+        EventMessage::AssignedId { user_id } => user_id,
+        _ => unreachable!(),
+    }
+}
+
+/// Connect and `Join` as `username`, returning the socket and assigned id.
+pub async fn join(connector: &MemoryConnector, username: &str) -> (Ws, uuid::Uuid) {
+    let mut ws = connect(connector).await;
+    let id = join_with(&mut ws, username).await;
+    (ws, id)
+}
+
+/// Poll `check` until it returns true, failing after a few seconds.
+pub async fn eventually<F, Fut>(check: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if check().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("condition not met in time");
+}
+
+/// Send one HTTP request and return the status code and body.
+pub async fn http(addr: SocketAddr, method: &str, path: &str, token: Option<&str>, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let auth = token.map(|t| format!("Authorization: Bearer {}\r\n", t)).unwrap_or_default();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        auth,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").map(|(_, b)| b.to_string()).unwrap_or_default();
+    (status, body)
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{eventually, join, recv_until, send, spawn};
+use rws_common::{EventMessage, RoomInfo};
+use rws_core::{HeartbeatConfig, Server, ServerConfig};
+
+fn join_room(id: uuid::Uuid) -> EventMessage {
+    EventMessage::JoinRoom {
+        user: None,
+        room: RoomInfo { id, name: String::new() },
+    }
+}
+
+#[tokio::test]
+async fn members_see_leave_and_presence_when_peer_drops() {
+    let (server, connector) = Server::memory();
+    let room_manager = server.room_manager();
+    spawn(server);
+
+    let (mut alice, _) = join(&connector, "alice").await;
+    let (mut bob, bob_id) = join(&connector, "bob").await;
+
+    send(&mut alice, &EventMessage::CreateRoom { creator: None, room_name: "lobby".into(), topic: None, visibility: Default::default() }).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::RoomCreated { .. })).await;
+    let room_id = *room_manager.rooms.iter().next().unwrap().key();
+
+    send(&mut bob, &join_room(room_id)).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::JoinRoom { .. })).await;
+
+    drop(bob);
+
+    let leave = recv_until(&mut alice, |e| matches!(e, EventMessage::LeaveRoom { .. })).await;
+    assert!(matches!(leave, EventMessage::LeaveRoom { user: Some(u), .. } if u.id == bob_id));
+
+    let gone = recv_until(&mut alice, |e| matches!(e, EventMessage::Disconnected { .. })).await;
+    assert!(matches!(gone, EventMessage::Disconnected { user } if user.id == bob_id && user.username == "bob"));
+
+    assert_eq!(room_manager.members(&room_id).len(), 1);
+    assert!(!room_manager.user_rooms.contains_key(&bob_id));
+}
+
+#[tokio::test]
+async fn connect_disconnect_cycles_leave_no_state_behind() {
+    let (server, connector) = Server::memory();
+    let clients = server.clients();
+    let room_manager = server.room_manager();
+    spawn(server);
+
+    for cycle in 0..20 {
+        let (mut owner, _) = join(&connector, &format!("owner{}", cycle)).await;
+        send(&mut owner, &EventMessage::CreateRoom { creator: None, room_name: format!("room{}", cycle), topic: None, visibility: Default::default() }).await;
+        recv_until(&mut owner, |e| matches!(e, EventMessage::RoomCreated { .. })).await;
+        let room_id = room_manager.user_rooms.iter().next().unwrap().value()[0];
+
+        let mut guests = Vec::new();
+        for n in 0..5 {
+            let (mut guest, _) = join(&connector, &format!("guest{}-{}", cycle, n)).await;
+            send(&mut guest, &join_room(room_id)).await;
+            recv_until(&mut guest, |e| matches!(e, EventMessage::JoinRoom { .. })).await;
+            guests.push(guest);
+        }
+
+        drop(owner);
+        drop(guests);
+
+        eventually(|| async {
+            clients.is_empty() && room_manager.rooms.is_empty() && room_manager.user_rooms.is_empty()
+        })
+        .await;
+    }
+}
+
+#[tokio::test]
+async fn silent_peers_are_reaped_through_the_disconnect_path() {
+    let config = ServerConfig {
+        heartbeat: HeartbeatConfig { interval: Duration::from_millis(100), timeout: Duration::from_millis(500) },
+        ..Default::default()
+    };
+    let (server, connector) = Server::memory();
+    let clients = server.clients();
+    let room_manager = server.room_manager();
+    spawn(server.with_config(config));
+
+    let (mut alice, alice_id) = join(&connector, "alice").await;
+    let (mut bob, bob_id) = join(&connector, "bob").await;
+
+    send(&mut alice, &EventMessage::CreateRoom { creator: None, room_name: "lobby".into(), topic: None, visibility: Default::default() }).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::RoomCreated { .. })).await;
+    let room_id = room_manager.get_user_rooms(&alice_id)[0];
+    send(&mut bob, &join_room(room_id)).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::JoinRoom { .. })).await;
+
+    // Bob stays connected but stops reading, so he never answers a ping.
+    // Alice keeps reading, which answers hers
+    let leave = recv_until(&mut alice, |e| matches!(e, EventMessage::LeaveRoom { .. })).await;
+    assert!(matches!(leave, EventMessage::LeaveRoom { user: Some(u), .. } if u.id == bob_id));
+    let gone = recv_until(&mut alice, |e| matches!(e, EventMessage::Disconnected { .. })).await;
+    assert!(matches!(gone, EventMessage::Disconnected { user } if user.id == bob_id));
+
+    assert!(!clients.contains(&bob_id));
+    assert!(!room_manager.user_rooms.contains_key(&bob_id));
+    assert!(!room_manager.is_member(&room_id, &bob_id));
+    drop(bob);
+}
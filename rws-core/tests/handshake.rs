@@ -0,0 +1,54 @@
+mod common;
+
+use common::{eventually, recv_until, send, spawn};
+use rws_common::{ChatScope, EventMessage};
+use rws_core::Server;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Write `bytes`, half-close, and return whatever the server answers.
+async fn raw(addr: std::net::SocketAddr, bytes: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(bytes).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn bad_handshakes_do_not_disturb_other_clients() {
+    let server = Server::bind("127.0.0.1:0").await.unwrap();
+    let addr = server.local_addr().unwrap();
+    let metrics = server.metrics();
+    spawn(server);
+
+    let url = format!("ws://{}", addr);
+    let (mut alice, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    common::join_with(&mut alice, "alice").await;
+
+    // Plain TCP that never speaks HTTP
+    assert!(raw(addr, b"\x00\x01\x02 not http at all").await.starts_with("HTTP/1.1 400"));
+    // A request line with no head terminator before the peer hangs up
+    assert!(raw(addr, b"GARBAGE / SPDY/9\r\n").await.starts_with("HTTP/1.1 400"));
+    // An upgrade missing Sec-WebSocket-Key
+    raw(addr, b"GET / HTTP/1.1\r\nHost: x\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n").await;
+    // Plain HTTP to a path that is not a probe
+    assert!(raw(addr, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n").await.starts_with("HTTP/1.1 426"));
+
+    eventually(|| async {
+        metrics.handshake_failures("bad_request") == 2
+            && metrics.handshake_failures("websocket") == 1
+            && metrics.handshake_failures("not_websocket") == 1
+    })
+    .await;
+
+    let (mut bob, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+    common::join_with(&mut bob, "bob").await;
+
+    let chat = EventMessage::Chat { id: uuid::Uuid::new_v4(), sender: None, content: "still here".into(), scope: ChatScope::Global };
+    send(&mut alice, &chat).await;
+    recv_until(&mut bob, |e| matches!(e, EventMessage::Chat { content, .. } if content == "still here")).await;
+}
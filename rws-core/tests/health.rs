@@ -0,0 +1,51 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{http, recv_until, spawn};
+use rws_common::EventMessage;
+use rws_core::{hook, EventHandlers, Server, ServerConfig, ShutdownNotice};
+
+#[tokio::test]
+async fn probes_are_answered_on_the_websocket_listener() {
+    let server = Server::bind("127.0.0.1:0").await.unwrap();
+    let addr = server.local_addr().unwrap();
+    spawn(server);
+
+    assert_eq!(http(addr, "GET", "/healthz", None, "").await, (200, "ok\n".to_string()));
+    assert_eq!(http(addr, "GET", "/readyz", None, "").await.0, 200);
+    assert_eq!(http(addr, "GET", "/", None, "").await.0, 426);
+
+    // Upgrades still work after the head has been inspected
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap();
+    common::join_with(&mut ws, "alice").await;
+}
+
+#[tokio::test]
+async fn readiness_fails_while_draining() {
+    let mut config = ServerConfig::default();
+    config.shutdown.drain_timeout = Duration::from_secs(5);
+    // A slow disconnect hook keeps the server draining while we probe it
+    let handlers = EventHandlers {
+        on_disconnect: Some(hook(|_ctx, ()| tokio::time::sleep(Duration::from_secs(2)))),
+        ..Default::default()
+    };
+    let server = Server::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .with_config(config)
+        .with_handlers(handlers);
+    let addr = server.local_addr().unwrap();
+    let handle = server.shutdown_handle();
+    spawn(server);
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.unwrap();
+    common::join_with(&mut ws, "alice").await;
+
+    handle.trigger(ShutdownNotice::default());
+    recv_until(&mut ws, |e| matches!(e, EventMessage::ServerShutdown { .. })).await;
+
+    assert_eq!(http(addr, "GET", "/readyz", None, "").await.0, 503);
+    assert_eq!(http(addr, "GET", "/healthz", None, "").await.0, 200);
+    assert!(tokio_tungstenite::connect_async(format!("ws://{}", addr)).await.is_err());
+}
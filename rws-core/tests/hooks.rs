@@ -0,0 +1,49 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{join, recv_until, send, spawn};
+use rws_common::{ChatScope, EventMessage, RoomInfo};
+use rws_core::{hook, EventHandlers, HookContext, Server};
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn registered_hooks_fire_with_the_sender() {
+    let (seen_tx, mut seen) = mpsc::unbounded_channel();
+    let on_connect = seen_tx.clone();
+    let on_chat = seen_tx;
+    let handlers = EventHandlers {
+        on_connect: Some(hook(move |ctx: HookContext, ()| {
+            let seen = on_connect.clone();
+            async move {
+                let _ = seen.send(("connect", ctx.sender_id));
+            }
+        })),
+        on_chat: Some(hook(move |ctx: HookContext, event: EventMessage| {
+            let seen = on_chat.clone();
+            async move {
+                let label = match event {
+                    EventMessage::Chat { content, .. } if content == "hooked" => "chat",
+                    _ => "unexpected chat",
+                };
+                let _ = seen.send((label, ctx.sender_id));
+            }
+        })),
+        ..Default::default()
+    };
+
+    let (server, connector) = Server::memory();
+    spawn(server.with_handlers(handlers));
+
+    let (mut alice, alice_id) = join(&connector, "alice").await;
+    // Refused, since alice is in no room, so it must not reach the hook
+    let nowhere = ChatScope::Room { room: RoomInfo { id: uuid::Uuid::new_v4(), name: String::new() } };
+    send(&mut alice, &EventMessage::Chat { id: uuid::Uuid::new_v4(), sender: None, content: "refused".into(), scope: nowhere }).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::Error { .. })).await;
+    send(&mut alice, &EventMessage::Chat { id: uuid::Uuid::new_v4(), sender: None, content: "hooked".into(), scope: ChatScope::Global }).await;
+
+    for expected in [("connect", alice_id), ("chat", alice_id)] {
+        let fired = tokio::time::timeout(Duration::from_secs(5), seen.recv()).await.unwrap();
+        assert_eq!(fired, Some(expected));
+    }
+}
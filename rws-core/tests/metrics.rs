@@ -0,0 +1,36 @@
+mod common;
+
+use common::{http, join, recv_until, send, spawn};
+use rws_common::{ChatScope, EventMessage, RoomInfo};
+use rws_core::Server;
+
+#[tokio::test]
+async fn metrics_endpoint_reports_traffic_and_errors() {
+    let (server, connector) = Server::memory();
+    let server = server.with_metrics_endpoint("127.0.0.1:0").await.unwrap();
+    let metrics_addr = server.metrics_addr().unwrap();
+    spawn(server);
+
+    let (mut alice, _) = join(&connector, "alice").await;
+    let (mut bob, _) = join(&connector, "bob").await;
+
+    let id = uuid::Uuid::new_v4();
+    let chat = EventMessage::Chat { id, sender: None, content: "hi".into(), scope: ChatScope::Global };
+    send(&mut alice, &chat).await;
+    recv_until(&mut bob, |e| matches!(e, EventMessage::Chat { .. })).await;
+
+    let missing = RoomInfo { id: uuid::Uuid::new_v4(), name: String::new() };
+    send(&mut bob, &EventMessage::JoinRoom { user: None, room: missing }).await;
+    recv_until(&mut bob, |e| matches!(e, EventMessage::Error { .. })).await;
+
+    let (status, response) = http(metrics_addr, "GET", "/metrics", None, "").await;
+    assert_eq!(status, 200);
+    assert!(response.contains("rws_connected_clients 2"));
+    assert!(response.contains("rws_connections_total 2"));
+    assert!(response.contains("rws_messages_total{scope=\"global\"} 1"));
+    assert!(response.contains("rws_errors_total{code=\"room_not_found\"} 1"));
+    assert!(response.contains("rws_dispatch_duration_seconds_count"));
+    assert!(!response.contains("rws_received_bytes_total 0"));
+
+    assert_eq!(http(metrics_addr, "GET", "/", None, "").await.0, 404);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{join, recv_until, send, spawn};
+use futures_util::{SinkExt, StreamExt};
+use rws_common::{ChatScope, ErrorCode, EventMessage, UserInfo};
+use rws_core::{Server, ServerConfig};
+use tokio_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message as WsMessage};
+
+#[tokio::test]
+async fn protocol_violations_are_reported_then_disconnected() {
+    let mut config = ServerConfig::default();
+    config.limits.max_protocol_violations = 3;
+    let (server, connector) = Server::memory();
+    spawn(server.with_config(config));
+
+    let (mut ws, _) = join(&connector, "alice").await;
+
+    ws.send(WsMessage::Text("{\n  \"event\": ".into())).await.unwrap();
+    match recv_until(&mut ws, |e| matches!(e, EventMessage::Error { .. })).await {
+        EventMessage::Error { error: ErrorCode::MalformedMessage { line, column, .. } } => {
+            assert_eq!((line, column), (2, 11));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    ws.send(WsMessage::Binary(vec![1, 2, 3])).await.unwrap();
+    let error = recv_until(&mut ws, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::UnsupportedEvent { .. } }));
+
+    let server_only = EventMessage::AssignedId { user_id: uuid::Uuid::new_v4() };
+    common::send(&mut ws, &server_only).await;
+    let error = recv_until(&mut ws, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::UnsupportedEvent { .. } }));
+
+    // The third violation closes the connection
+    let close = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while let Some(Ok(msg)) = ws.next().await {
+            if let WsMessage::Close(frame) = msg {
+                return frame;
+            }
+        }
+        None
+    })
+    .await
+    .unwrap();
+    assert_eq!(close.unwrap().code, CloseCode::Policy);
+}
+
+#[tokio::test]
+async fn payloads_naming_another_user_are_refused_without_effect() {
+    let (server, connector) = Server::memory();
+    let room_manager = server.room_manager();
+    spawn(server);
+
+    let (mut alice, alice_id) = join(&connector, "alice").await;
+    let (mut bob, bob_id) = join(&connector, "bob").await;
+
+    send(&mut alice, &EventMessage::CreateRoom { creator: None, room_name: "lobby".into(), topic: None, visibility: Default::default() }).await;
+    let room = match recv_until(&mut alice, |e| matches!(e, EventMessage::RoomCreated { .. })).await {
+        EventMessage::RoomCreated { room } => room,
+        _ => unreachable!(),
+    };
+
+    // Bob tries to pull himself in as Alice, then to speak as her
+    let alice_info = UserInfo { id: alice_id, username: "alice".into() };
+    send(&mut bob, &EventMessage::JoinRoom { user: Some(alice_info.clone()), room: room.clone() }).await;
+    let error = recv_until(&mut bob, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::IdentityMismatch { .. } }));
+
+    let forged = EventMessage::Chat { id: uuid::Uuid::new_v4(), sender: Some(alice_info), content: "forged".into(), scope: ChatScope::Global };
+    send(&mut bob, &forged).await;
+    let error = recv_until(&mut bob, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::IdentityMismatch { .. } }));
+
+    assert_eq!(room_manager.members(&room.id), [alice_id]);
+    assert!(!room_manager.is_member(&room.id, &bob_id));
+
+    // Naming yourself is fine; Alice sees this chat and never saw the forged one
+    let honest = EventMessage::Chat { id: uuid::Uuid::new_v4(), sender: Some(UserInfo { id: bob_id, username: "bob".into() }), content: "honest".into(), scope: ChatScope::Global };
+    send(&mut bob, &honest).await;
+    let received = recv_until(&mut alice, |e| matches!(e, EventMessage::Chat { .. })).await;
+    assert!(matches!(received, EventMessage::Chat { content, .. } if content == "honest"));
+}
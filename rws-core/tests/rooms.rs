@@ -0,0 +1,289 @@
+mod common;
+
+use common::{join, recv_until, send, spawn, Ws};
+use rws_common::{ChatScope, ErrorCode, EventMessage, RoomInfo, RoomListing, RoomRole, RoomVisibility, PROTOCOL_VERSION};
+use rws_core::Server;
+
+async fn create_room(ws: &mut Ws, name: &str) -> RoomInfo {
+    send(ws, &EventMessage::CreateRoom { creator: None, room_name: name.to_string(), topic: None, visibility: Default::default() }).await;
+    match recv_until(ws, |e| matches!(e, EventMessage::JoinRoom { .. })).await {
+        EventMessage::JoinRoom { room, .. } => room,
+        _ => unreachable!(),
+    }
+}
+
+fn chat(content: &str, scope: ChatScope) -> EventMessage {
+    EventMessage::Chat { id: uuid::Uuid::new_v4(), sender: None, content: content.to_string(), scope }
+}
+
+fn in_room(room: &RoomInfo) -> ChatScope {
+    ChatScope::Room { room: RoomInfo { id: room.id, name: String::new() } }
+}
+
+#[tokio::test]
+async fn users_chat_in_several_rooms_at_once() {
+    let (server, connector) = Server::memory();
+    let room_manager = server.room_manager();
+    spawn(server);
+
+    let (mut alice, alice_id) = join(&connector, "alice").await;
+    send(&mut alice, &EventMessage::Hello { protocol_version: PROTOCOL_VERSION, client_name: "test".into(), capabilities: Vec::new() }).await;
+    let (mut bob, _) = join(&connector, "bob").await;
+    let (mut carol, _) = join(&connector, "carol").await;
+
+    // Being in a room no longer stops you from creating another
+    let general = create_room(&mut alice, "general").await;
+    let random = create_room(&mut alice, "random").await;
+    assert_eq!(room_manager.get_user_rooms(&alice_id), vec![general.id, random.id]);
+
+    send(&mut bob, &EventMessage::JoinRoom { user: None, room: general.clone() }).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::JoinRoom { .. })).await;
+
+    send(&mut alice, &chat("to random", in_room(&random))).await;
+    send(&mut alice, &chat("to general", in_room(&general))).await;
+    let received = recv_until(&mut bob, |e| matches!(e, EventMessage::Chat { .. })).await;
+    assert!(matches!(
+        received,
+        EventMessage::Chat { content, scope: ChatScope::Room { room }, .. } if content == "to general" && room.name == "general"
+    ));
+
+    // Untargeted chat from a current client is global, even while in rooms
+    send(&mut alice, &chat("hello all", ChatScope::Global)).await;
+    recv_until(&mut carol, |e| matches!(e, EventMessage::Chat { content, .. } if content == "hello all")).await;
+
+    // Only members may post to a room
+    send(&mut carol, &chat("let me in", in_room(&general))).await;
+    let error = recv_until(&mut carol, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::RoomNotFound { .. } }));
+
+    // Leaving one room keeps the other membership
+    send(&mut alice, &EventMessage::LeaveRoom { user: None, room: general.clone() }).await;
+    recv_until(&mut bob, |e| matches!(e, EventMessage::LeaveRoom { .. })).await;
+    assert_eq!(room_manager.get_user_rooms(&alice_id), vec![random.id]);
+    assert!(!room_manager.is_member(&general.id, &alice_id));
+}
+
+#[tokio::test]
+async fn legacy_clients_chat_in_the_room_they_joined_last() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+
+    // Clients that never say Hello keep the single-room behaviour
+    let (mut alice, _) = join(&connector, "alice").await;
+    let (mut bob, _) = join(&connector, "bob").await;
+    let room = create_room(&mut alice, "general").await;
+    send(&mut bob, &EventMessage::JoinRoom { user: None, room: room.clone() }).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::JoinRoom { .. })).await;
+
+    send(&mut bob, &chat("hi", ChatScope::Global)).await;
+    let received = recv_until(&mut alice, |e| matches!(e, EventMessage::Chat { .. })).await;
+    assert!(matches!(received, EventMessage::Chat { scope: ChatScope::Room { room: r }, .. } if r.id == room.id));
+
+    let nil = RoomInfo { id: uuid::Uuid::nil(), name: String::new() };
+    send(&mut bob, &EventMessage::LeaveRoom { user: None, room: nil }).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::LeaveRoom { .. })).await;
+}
+
+async fn list_rooms(ws: &mut Ws, filter: Option<&str>, cursor: Option<String>) -> (Vec<RoomListing>, Option<String>) {
+    send(ws, &EventMessage::ListRooms { filter: filter.map(str::to_string), cursor }).await;
+    match recv_until(ws, |e| matches!(e, EventMessage::RoomList { .. })).await {
+        EventMessage::RoomList { rooms, next_cursor } => (rooms, next_cursor),
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn rooms_can_be_listed_searched_and_paged() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+
+    let (mut alice, _) = join(&connector, "alice").await;
+    let (mut bob, _) = join(&connector, "bob").await;
+
+    send(&mut alice, &EventMessage::CreateRoom {
+        creator: None,
+        room_name: "secret".into(),
+        topic: None,
+        visibility: RoomVisibility::Private,
+    })
+    .await;
+    let EventMessage::RoomCreated { room: secret } = recv_until(&mut alice, |e| matches!(e, EventMessage::RoomCreated { .. })).await else {
+        unreachable!()
+    };
+    assert_eq!(secret.name, "secret");
+
+    send(&mut alice, &EventMessage::CreateRoom {
+        creator: None,
+        room_name: "Rustaceans".into(),
+        topic: Some("All things Rust".into()),
+        visibility: RoomVisibility::Public,
+    })
+    .await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::RoomCreated { .. })).await;
+    for n in 0..55 {
+        create_room(&mut alice, &format!("room{:02}", n)).await;
+    }
+
+    // Private rooms are hidden from non-members
+    let (first, cursor) = list_rooms(&mut bob, None, None).await;
+    assert_eq!(first.len(), 50);
+    assert_eq!(first[0].name, "room00");
+    let (rest, end) = list_rooms(&mut bob, None, cursor).await;
+    assert_eq!(rest.len(), 6);
+    assert!(end.is_none());
+    assert_eq!(rest.last().unwrap().name, "Rustaceans");
+    assert!(first.iter().chain(&rest).all(|r| r.id != secret.id));
+
+    let (found, _) = list_rooms(&mut bob, Some("RUST THINGS"), None).await;
+    assert!(found.is_empty());
+    let (found, _) = list_rooms(&mut bob, Some("rust"), None).await;
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].member_count, found[0].topic.as_deref()), (1, Some("All things Rust")));
+
+    let (mine, _) = list_rooms(&mut alice, Some("secret"), None).await;
+    assert_eq!(mine[0].visibility, RoomVisibility::Private);
+
+    send(&mut bob, &EventMessage::ListRooms { filter: None, cursor: Some("bogus".into()) }).await;
+    let error = recv_until(&mut bob, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::InvalidCursor { .. } }));
+}
+
+#[tokio::test]
+async fn room_names_are_unique_and_joinable_by_slug() {
+    let (server, connector) = Server::memory();
+    let room_manager = server.room_manager();
+    spawn(server);
+
+    let (mut alice, _) = join(&connector, "alice").await;
+    let (mut bob, bob_id) = join(&connector, "bob").await;
+
+    let backend = create_room(&mut alice, "Backend  Team").await;
+    assert_eq!(room_manager.get_room(&backend.id).unwrap().slug, "backend-team");
+
+    send(&mut bob, &EventMessage::CreateRoom { creator: None, room_name: "#backend_team".into(), topic: None, visibility: Default::default() }).await;
+    let error = recv_until(&mut bob, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::RoomAlreadyExists { .. } }));
+
+    send(&mut bob, &EventMessage::CreateRoom { creator: None, room_name: "#!?".into(), topic: None, visibility: Default::default() }).await;
+    let error = recv_until(&mut bob, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::InvalidRoomName { .. } }));
+
+    send(&mut bob, &EventMessage::JoinRoomByName { user: None, room_name: "#Backend-Team".into() }).await;
+    let joined = recv_until(&mut bob, |e| matches!(e, EventMessage::JoinRoom { .. })).await;
+    assert!(matches!(joined, EventMessage::JoinRoom { room, .. } if room.id == backend.id));
+    assert!(room_manager.is_member(&backend.id, &bob_id));
+
+    send(&mut bob, &EventMessage::JoinRoomByName { user: None, room_name: "frontend".into() }).await;
+    let error = recv_until(&mut bob, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::RoomNotFound { .. } }));
+
+    // The name is freed once the room empties
+    for ws in [&mut alice, &mut bob] {
+        send(ws, &EventMessage::LeaveRoom { user: None, room: backend.clone() }).await;
+    }
+    common::eventually(|| async { room_manager.find_by_name("backend team").is_none() }).await;
+    create_room(&mut bob, "backend-team").await;
+}
+
+#[tokio::test]
+async fn joining_sends_the_roster_and_it_can_be_requested() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+
+    let (mut alice, alice_id) = join(&connector, "alice").await;
+    let (mut bob, bob_id) = join(&connector, "bob").await;
+    let (mut carol, _) = join(&connector, "carol").await;
+
+    let room = create_room(&mut alice, "general").await;
+    let roster = recv_until(&mut alice, |e| matches!(e, EventMessage::RoomMembers { .. })).await;
+    assert!(matches!(roster, EventMessage::RoomMembers { members, .. } if members.len() == 1));
+
+    send(&mut bob, &EventMessage::JoinRoom { user: None, room: room.clone() }).await;
+    let EventMessage::RoomMembers { members, roles, .. } = recv_until(&mut bob, |e| matches!(e, EventMessage::RoomMembers { .. })).await else {
+        unreachable!()
+    };
+    let names: Vec<_> = members.iter().map(|m| m.username.as_str()).collect();
+    assert_eq!(names, ["alice", "bob"]);
+    assert_eq!(roles[&alice_id], RoomRole::Owner);
+    assert_eq!(roles[&bob_id], RoomRole::Member);
+
+    // Existing members get the change, not a new snapshot
+    recv_until(&mut alice, |e| matches!(e, EventMessage::JoinRoom { user: Some(u), .. } if u.id == bob_id)).await;
+
+    send(&mut carol, &EventMessage::GetRoomMembers { room: room.clone() }).await;
+    let error = recv_until(&mut carol, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::RoomNotFound { .. } }));
+
+    send(&mut alice, &EventMessage::GetRoomMembers { room }).await;
+    let roster = recv_until(&mut alice, |e| matches!(e, EventMessage::RoomMembers { .. })).await;
+    assert!(matches!(roster, EventMessage::RoomMembers { members, .. } if members.len() == 2));
+}
+
+#[tokio::test]
+async fn moderators_kick_and_ban_and_ownership_passes_on() {
+    let (server, connector) = Server::memory();
+    let room_manager = server.room_manager();
+    spawn(server);
+
+    let (mut alice, alice_id) = join(&connector, "alice").await;
+    let (mut bob, bob_id) = join(&connector, "bob").await;
+    let (mut carol, carol_id) = join(&connector, "carol").await;
+    for ws in [&mut alice, &mut bob, &mut carol] {
+        send(ws, &EventMessage::Hello { protocol_version: PROTOCOL_VERSION, client_name: "test".into(), capabilities: Vec::new() }).await;
+        recv_until(ws, |e| matches!(e, EventMessage::Welcome { .. })).await;
+    }
+    // Dave never says hello, so his client predates moderation
+    let (mut dave, _) = join(&connector, "dave").await;
+
+    let room = create_room(&mut alice, "general").await;
+    for ws in [&mut bob, &mut carol, &mut dave] {
+        send(ws, &EventMessage::JoinRoom { user: None, room: room.clone() }).await;
+        recv_until(ws, |e| matches!(e, EventMessage::RoomMembers { .. })).await;
+    }
+
+    // Plain members cannot moderate
+    send(&mut carol, &EventMessage::KickMember { room: room.clone(), user_id: bob_id, reason: None }).await;
+    let error = recv_until(&mut carol, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::PermissionDenied { .. } }));
+
+    send(&mut alice, &EventMessage::SetRoomRole { room: room.clone(), user_id: bob_id, role: RoomRole::Moderator }).await;
+    recv_until(&mut bob, |e| matches!(e, EventMessage::RoomRoleChanged { role: RoomRole::Moderator, .. })).await;
+    send(&mut dave, &EventMessage::GetRoomMembers { room: room.clone() }).await;
+    let roster = recv_until(&mut dave, |e| {
+        assert!(!matches!(e, EventMessage::RoomRoleChanged { .. }), "{:?}", e);
+        matches!(e, EventMessage::RoomMembers { .. })
+    })
+    .await;
+    assert!(matches!(roster, EventMessage::RoomMembers { roles, .. } if roles[&bob_id] == RoomRole::Member));
+
+    send(&mut bob, &EventMessage::KickMember { room: room.clone(), user_id: carol_id, reason: Some("spam".into()) }).await;
+    let kicked = recv_until(&mut carol, |e| matches!(e, EventMessage::MemberKicked { .. })).await;
+    assert!(matches!(kicked, EventMessage::MemberKicked { by, reason: Some(r), .. } if by.id == bob_id && r == "spam"));
+    recv_until(&mut carol, |e| matches!(e, EventMessage::LeaveRoom { user: Some(u), .. } if u.id == carol_id)).await;
+
+    // Moderators cannot act on the owner, but can ban members who already left
+    send(&mut bob, &EventMessage::KickMember { room: room.clone(), user_id: alice_id, reason: None }).await;
+    let error = recv_until(&mut bob, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::PermissionDenied { .. } }));
+
+    send(&mut bob, &EventMessage::BanMember { room: room.clone(), user_id: carol_id, reason: None, duration_secs: None }).await;
+    recv_until(&mut carol, |e| matches!(e, EventMessage::MemberBanned { .. })).await;
+    send(&mut carol, &EventMessage::JoinRoom { user: None, room: room.clone() }).await;
+    let error = recv_until(&mut carol, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(error, EventMessage::Error { error: ErrorCode::PermissionDenied { .. } }));
+    assert!(!room_manager.is_member(&room.id, &carol_id));
+
+    // The owner leaving hands the room to the moderator
+    send(&mut alice, &EventMessage::LeaveRoom { user: None, room: room.clone() }).await;
+    recv_until(&mut bob, |e| matches!(e, EventMessage::RoomRoleChanged { user, role: RoomRole::Owner, .. } if user.id == bob_id)).await;
+    assert_eq!(room_manager.get_room(&room.id).unwrap().owner_id, bob_id);
+
+    // Dave only saw the departures, never events his client cannot parse
+    send(&mut dave, &EventMessage::GetRoomMembers { room: room.clone() }).await;
+    let roster = recv_until(&mut dave, |e| {
+        assert!(!matches!(e, EventMessage::RoomRoleChanged { .. } | EventMessage::MemberKicked { .. } | EventMessage::MemberBanned { .. }), "{:?}", e);
+        matches!(e, EventMessage::RoomMembers { .. })
+    })
+    .await;
+    assert!(matches!(roster, EventMessage::RoomMembers { roles, .. } if roles[&bob_id] == RoomRole::Owner));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{join, recv_until};
+use futures_util::StreamExt;
+use rws_common::EventMessage;
+use rws_core::{Server, ShutdownNotice};
+use tokio_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message as WsMessage};
+
+#[tokio::test]
+async fn shutdown_notifies_clients_and_sends_close_frames() {
+    let (server, connector) = Server::memory();
+    let clients = server.clients();
+    let handle = server.shutdown_handle();
+    let running = tokio::spawn(server.run());
+
+    let (mut alice, _) = join(&connector, "alice").await;
+
+    handle.trigger(ShutdownNotice {
+        reconnect_after: Some(Duration::from_secs(30)),
+    });
+
+    let notice = recv_until(&mut alice, |e| matches!(e, EventMessage::ServerShutdown { .. })).await;
+    assert!(matches!(notice, EventMessage::ServerShutdown { reconnect_after_secs: Some(30) }));
+
+    match alice.next().await {
+        Some(Ok(WsMessage::Close(Some(frame)))) => assert_eq!(frame.code, CloseCode::Away),
+        other => panic!("expected close frame, got {:?}", other),
+    }
+
+    tokio::time::timeout(Duration::from_secs(5), running)
+        .await
+        .expect("server did not stop")
+        .unwrap()
+        .unwrap();
+    assert!(clients.is_empty());
+    assert!(connector.connect().is_err());
+}
+
+#[tokio::test]
+async fn shutdown_triggered_before_the_server_runs_still_stops_it() {
+    let (server, _connector) = Server::memory();
+    let handle = server.shutdown_handle();
+    // The test runtime is single-threaded, so the task has not been polled yet
+    let running = tokio::spawn(server.run());
+    handle.trigger(ShutdownNotice::default());
+
+    tokio::time::timeout(Duration::from_secs(5), running)
+        .await
+        .expect("server did not stop")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn run_until_stops_when_the_signal_is_already_ready() {
+    let (server, _connector) = Server::memory();
+    tokio::time::timeout(Duration::from_secs(5), server.run_until(std::future::ready(())))
+        .await
+        .expect("server did not stop")
+        .unwrap();
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::sync::Arc;
+
+use rws_common::EventMessage;
+use rws_core::{tls, Server};
+use tokio_rustls::rustls::{self, Certificate, RootCertStore};
+use tokio_tungstenite::{connect_async_tls_with_config, Connector};
+
+use common::{join_with, recv_until, send};
+
+/// Self-signed certificate for `localhost`, plus a client config trusting it.
+fn self_signed() -> (tls::TlsAcceptor, Arc<rustls::ClientConfig>) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let acceptor = tls::acceptor_from_pem(
+        cert.serialize_pem().unwrap().as_bytes(),
+        cert.serialize_private_key_pem().as_bytes(),
+    )
+    .unwrap();
+
+    let mut roots = RootCertStore::empty();
+    roots.add(&Certificate(cert.serialize_der().unwrap())).unwrap();
+    let client = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    (acceptor, Arc::new(client))
+}
+
+#[tokio::test]
+async fn clients_can_join_and_chat_over_wss() {
+    let (acceptor, client_config) = self_signed();
+    let server = Server::bind("127.0.0.1:0").await.unwrap().with_tls(acceptor);
+    let port = server.local_addr().unwrap().port();
+    common::spawn(server);
+
+    let (mut ws, _) = connect_async_tls_with_config(
+        format!("wss://localhost:{}", port),
+        None,
+        false,
+        Some(Connector::Rustls(client_config)),
+    )
+    .await
+    .unwrap();
+
+    join_with(&mut ws, "alice").await;
+
+    let id = uuid::Uuid::new_v4();
+    send(
+        &mut ws,
+        &EventMessage::Chat {
+            id,
+            sender: None,
+            content: "over tls".to_string(),
+            scope: rws_common::ChatScope::Global,
+        },
+    )
+    .await;
+    recv_until(&mut ws, |e| matches!(e, EventMessage::AckDelivered { id: acked } if *acked == id)).await;
+}
+
+#[tokio::test]
+async fn plain_clients_are_refused_without_affecting_the_server() {
+    let (acceptor, client_config) = self_signed();
+    let server = Server::bind("127.0.0.1:0").await.unwrap().with_tls(acceptor);
+    let port = server.local_addr().unwrap().port();
+    common::spawn(server);
+
+    assert!(tokio_tungstenite::connect_async(format!("ws://localhost:{}", port)).await.is_err());
+
+    let connected = connect_async_tls_with_config(
+        format!("wss://localhost:{}", port),
+        None,
+        false,
+        Some(Connector::Rustls(client_config)),
+    )
+    .await;
+    assert!(connected.is_ok());
+}
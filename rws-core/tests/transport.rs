@@ -0,0 +1,87 @@
+mod common;
+
+use std::io;
+
+use common::{join, join_with, recv_until, send, spawn};
+use futures_util::{future::BoxFuture, FutureExt};
+use rws_common::{ChatScope, EventMessage};
+use rws_core::{
+    transport::{BoxedStream, ListenAddr},
+    Listener, MemoryListener, Server,
+};
+
+fn chat(content: &str) -> EventMessage {
+    EventMessage::Chat {
+        id: uuid::Uuid::new_v4(),
+        sender: None,
+        content: content.to_string(),
+        scope: ChatScope::Global,
+    }
+}
+
+#[tokio::test]
+async fn memory_transport_carries_broadcasts_between_clients() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+
+    let (mut alice, _) = join(&connector, "alice").await;
+    let (mut bob, _) = join(&connector, "bob").await;
+
+    send(&mut alice, &chat("hello bob")).await;
+    let received = recv_until(&mut bob, |e| matches!(e, EventMessage::Chat { .. })).await;
+    assert!(matches!(received, EventMessage::Chat { content, .. } if content == "hello bob"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket_listener_serves_clients_and_replaces_stale_socket() {
+    let path = std::env::temp_dir().join(format!("rws-{}.sock", uuid::Uuid::new_v4()));
+    // A leftover socket file from a process that is gone
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    let server = Server::bind_unix(&path).unwrap();
+    assert!(Server::bind_unix(&path).is_err(), "live socket must not be replaced");
+    spawn(server);
+
+    let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let (mut ws, _) = tokio_tungstenite::client_async("ws://localhost/", stream).await.unwrap();
+    join_with(&mut ws, "alice").await;
+
+    send(&mut ws, &chat("over a socket file")).await;
+    recv_until(&mut ws, |e| matches!(e, EventMessage::AckDelivered { .. })).await;
+
+    let _ = std::fs::remove_file(&path);
+}
+
+/// Fails its first `failures` accepts, then hands out in-memory connections.
+struct FlakyListener {
+    inner: MemoryListener,
+    failures: usize,
+}
+
+impl Listener for FlakyListener {
+    fn accept(&mut self) -> BoxFuture<'_, io::Result<BoxedStream>> {
+        if self.failures > 0 {
+            self.failures -= 1;
+            return async { Err(io::Error::other("too many open files")) }.boxed();
+        }
+        self.inner.accept()
+    }
+
+    fn local_addr(&self) -> io::Result<ListenAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[tokio::test]
+async fn accept_errors_do_not_stop_the_server() {
+    let (listener, connector) = rws_core::transport::memory();
+    let server = Server::with_listener(FlakyListener { inner: listener, failures: 3 });
+    let metrics = server.metrics();
+    spawn(server);
+
+    let (mut alice, _) = join(&connector, "alice").await;
+    send(&mut alice, &chat("still serving")).await;
+    recv_until(&mut alice, |e| matches!(e, EventMessage::AckDelivered { .. })).await;
+    assert_eq!(metrics.accept_errors(), 3);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{connect, join, join_with, recv_until, send, spawn};
+use rws_common::{ErrorCode, EventMessage, PROTOCOL_VERSION};
+use rws_core::{Server, ServerConfig, UsernameRules};
+
+fn rename(username: &str) -> EventMessage {
+    EventMessage::ChangeUsername { user: None, username: username.to_string() }
+}
+
+async fn error_of<S>(ws: &mut tokio_tungstenite::WebSocketStream<S>) -> ErrorCode
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    match recv_until(ws, |e| matches!(e, EventMessage::Error { .. })).await {
+        EventMessage::Error { error } => error,
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn usernames_are_validated_and_unique_ignoring_case() {
+    let config = ServerConfig {
+        username: UsernameRules { extra_chars: "_Éé".into(), ..Default::default() },
+        ..Default::default()
+    };
+    let (server, connector) = Server::memory();
+    spawn(server.with_config(config));
+
+    let (mut emile, _) = join(&connector, "Émile").await;
+
+    let mut other = connect(&connector).await;
+    for (name, expected) in [("x", "invalid_username"), ("bad name!", "invalid_username"), ("éMILE", "username_taken")] {
+        send(&mut other, &EventMessage::Join { username: name.into() }).await;
+        assert_eq!(error_of(&mut other).await.kind(), expected, "{}", name);
+    }
+
+    // Renaming to another spelling of the same name keeps it reserved
+    send(&mut emile, &rename("émile")).await;
+    recv_until(&mut emile, |e| matches!(e, EventMessage::ChangeUsername { .. })).await;
+    send(&mut other, &EventMessage::Join { username: "ÉMILE".into() }).await;
+    assert_eq!(error_of(&mut other).await.kind(), "username_taken");
+}
+
+#[tokio::test]
+async fn renames_are_broadcast_and_second_joins_refused() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+
+    let (mut alice, alice_id) = join(&connector, "alice").await;
+    let (mut bob, _) = join(&connector, "bob").await;
+
+    send(&mut alice, &rename("alicia")).await;
+    let change = recv_until(&mut bob, |e| matches!(e, EventMessage::ChangeUsername { .. })).await;
+    assert!(matches!(
+        change,
+        EventMessage::ChangeUsername { user: Some(u), username } if u.id == alice_id && u.username == "alice" && username == "alicia"
+    ));
+
+    send(&mut bob, &rename("alicia")).await;
+    assert_eq!(error_of(&mut bob).await.kind(), "username_taken");
+
+    // Joining again is not a way around ChangeUsername
+    send(&mut alice, &EventMessage::Join { username: "mallory".into() }).await;
+    assert_eq!(error_of(&mut alice).await.kind(), "permission_denied");
+
+    // Renaming needs a name to begin with
+    let mut anonymous = connect(&connector).await;
+    send(&mut anonymous, &rename("carol")).await;
+    assert_eq!(error_of(&mut anonymous).await.kind(), "permission_denied");
+    join_with(&mut anonymous, "carol").await;
+}
+
+#[tokio::test]
+async fn a_refused_join_can_be_retried_after_welcome() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+    let _alice = join(&connector, "alice").await;
+
+    // As the TUI does: Hello and Join back to back, then /nick after the refusal
+    let mut ws = connect(&connector).await;
+    send(&mut ws, &EventMessage::Hello { protocol_version: PROTOCOL_VERSION, client_name: "test".into(), capabilities: Vec::new() }).await;
+    send(&mut ws, &EventMessage::Join { username: "Alice".into() }).await;
+    recv_until(&mut ws, |e| matches!(e, EventMessage::Welcome { .. })).await;
+    assert_eq!(error_of(&mut ws).await.kind(), "username_taken");
+
+    join_with(&mut ws, "bob").await;
+    send(&mut ws, &rename("robert")).await;
+    recv_until(&mut ws, |e| matches!(e, EventMessage::ChangeUsername { username, .. } if username == "robert")).await;
+}
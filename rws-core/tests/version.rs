@@ -0,0 +1,79 @@
+mod common;
+
+use common::{connect, recv_until, send, spawn};
+use futures_util::StreamExt;
+use rws_common::{ErrorCode, EventMessage, PROTOCOL_VERSION};
+use rws_core::Server;
+use tokio_tungstenite::{
+    client_async,
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, protocol::frame::coding::CloseCode, Error as WsError, Message as WsMessage},
+};
+
+fn hello(protocol_version: u32) -> EventMessage {
+    EventMessage::Hello { protocol_version, client_name: "test".into(), capabilities: Vec::new() }
+}
+
+#[tokio::test]
+async fn hello_is_answered_with_welcome() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+
+    let mut ws = connect(&connector).await;
+    send(&mut ws, &hello(PROTOCOL_VERSION)).await;
+    let welcome = recv_until(&mut ws, |e| matches!(e, EventMessage::Welcome { .. })).await;
+    let EventMessage::Welcome { user_id, protocol_version, limits, .. } = welcome else { unreachable!() };
+    assert_eq!(protocol_version, PROTOCOL_VERSION);
+    assert_eq!(limits.max_message_size, 64 * 1024);
+
+    // Join still names the session and reports the same id
+    assert_eq!(common::join_with(&mut ws, "alice").await, user_id);
+}
+
+#[tokio::test]
+async fn unsupported_hello_version_is_refused_and_closed() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+
+    let mut ws = connect(&connector).await;
+    send(&mut ws, &hello(PROTOCOL_VERSION + 1)).await;
+    let error = recv_until(&mut ws, |e| matches!(e, EventMessage::Error { .. })).await;
+    assert!(matches!(
+        error,
+        EventMessage::Error { error: ErrorCode::UnsupportedVersion { max_version, .. } } if max_version == PROTOCOL_VERSION
+    ));
+
+    let close = loop {
+        match ws.next().await {
+            Some(Ok(WsMessage::Close(frame))) => break frame,
+            Some(Ok(_)) => continue,
+            other => panic!("expected close frame, got {:?}", other),
+        }
+    };
+    assert_eq!(close.unwrap().code, CloseCode::Protocol);
+}
+
+#[tokio::test]
+async fn subprotocol_header_negotiates_the_newest_shared_version() {
+    let (server, connector) = Server::memory();
+    spawn(server);
+
+    let mut request = "ws://memory/".into_client_request().unwrap();
+    let offer = format!("rws.v{}, rws.v{}", PROTOCOL_VERSION + 1, PROTOCOL_VERSION);
+    request.headers_mut().insert("Sec-WebSocket-Protocol", HeaderValue::from_str(&offer).unwrap());
+    let (mut ws, response) = client_async(request, connector.connect().unwrap()).await.unwrap();
+    assert_eq!(
+        response.headers().get("Sec-WebSocket-Protocol").unwrap(),
+        format!("rws.v{}", PROTOCOL_VERSION).as_str()
+    );
+
+    // Welcome arrives without a Hello
+    recv_until(&mut ws, |e| matches!(e, EventMessage::Welcome { .. })).await;
+
+    let mut request = "ws://memory/".into_client_request().unwrap();
+    let offer = format!("rws.v{}", PROTOCOL_VERSION + 1);
+    request.headers_mut().insert("Sec-WebSocket-Protocol", HeaderValue::from_str(&offer).unwrap());
+    match client_async(request, connector.connect().unwrap()).await {
+        Err(WsError::Http(response)) => assert_eq!(response.status(), 400),
+        other => panic!("expected 400, got {:?}", other.map(|(_, r)| r)),
+    }
+}
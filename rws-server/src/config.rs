@@ -0,0 +1,417 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use rws_core::{
+    HeartbeatConfig, LimitsConfig, LoggingConfig, OutboundConfig, ServerConfig, ShutdownConfig, SlowConsumerPolicy, UsernameRules,
+};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+
+/// Command-line flags. Every setting can also come from an `RWS_*`
+/// environment variable; flags win over the environment, which wins over the
+/// config file.
+#[derive(Debug, Parser)]
+#[command(name = "rws-server")]
+#[command(about = "RWS WebSocket chat server")]
+pub struct Args {
+    /// TOML config file
+    #[arg(short, long, env = "RWS_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration and exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Address to listen on, e.g. 0.0.0.0:3000 or unix:/run/rws.sock
+    #[arg(long, env = "RWS_BIND")]
+    pub bind: Option<String>,
+
+    #[arg(long, env = "RWS_HEARTBEAT_INTERVAL_SECS")]
+    pub heartbeat_interval_secs: Option<u64>,
+
+    #[arg(long, env = "RWS_HEARTBEAT_TIMEOUT_SECS")]
+    pub heartbeat_timeout_secs: Option<u64>,
+
+    #[arg(long, env = "RWS_MAX_MESSAGE_SIZE")]
+    pub max_message_size: Option<usize>,
+
+    #[arg(long, env = "RWS_MAX_ROOMS")]
+    pub max_rooms: Option<usize>,
+
+    #[arg(long, env = "RWS_MAX_ROOM_MEMBERS")]
+    pub max_room_members: Option<usize>,
+
+    #[arg(long, env = "RWS_MAX_ROOM_NAME_LEN")]
+    pub max_room_name_len: Option<usize>,
+
+    #[arg(long, env = "RWS_MAX_ROOM_TOPIC_LEN")]
+    pub max_room_topic_len: Option<usize>,
+
+    #[arg(long, env = "RWS_MAX_PROTOCOL_VIOLATIONS")]
+    pub max_protocol_violations: Option<u32>,
+
+    #[arg(long, env = "RWS_USERNAME_MIN_LEN")]
+    pub username_min_len: Option<usize>,
+
+    #[arg(long, env = "RWS_USERNAME_MAX_LEN")]
+    pub username_max_len: Option<usize>,
+
+    #[arg(long, env = "RWS_OUTBOUND_CAPACITY")]
+    pub outbound_capacity: Option<usize>,
+
+    /// drop_oldest, drop_newest or disconnect
+    #[arg(long, env = "RWS_SLOW_CONSUMER_POLICY", value_parser = parse_policy)]
+    pub slow_consumer_policy: Option<SlowConsumerPolicy>,
+
+    #[arg(long, env = "RWS_DRAIN_TIMEOUT_SECS")]
+    pub drain_timeout_secs: Option<u64>,
+
+    /// Secret for verifying HMAC bearer tokens; enables authentication
+    #[arg(long, env = "RWS_HMAC_SECRET", hide_env_values = true)]
+    pub hmac_secret: Option<String>,
+
+    /// Address for the Prometheus /metrics endpoint, e.g. 127.0.0.1:9100
+    #[arg(long, env = "RWS_METRICS_BIND")]
+    pub metrics_bind: Option<String>,
+
+    /// Address for the admin HTTP API; requires --admin-token
+    #[arg(long, env = "RWS_ADMIN_BIND")]
+    pub admin_bind: Option<String>,
+
+    /// Bearer token required by the admin API
+    #[arg(long, env = "RWS_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+
+    /// PEM certificate chain; serves wss:// together with --tls-key
+    #[arg(long, env = "RWS_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, env = "RWS_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Level or filter directives, e.g. info or warn,rws_core=debug
+    #[arg(long, env = "RWS_LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    /// text or json
+    #[arg(long, env = "RWS_LOG_FORMAT", value_parser = parse_log_format)]
+    pub log_format: Option<LogFormat>,
+
+    /// Include chat message content in logs (redacted by default)
+    #[arg(long, env = "RWS_LOG_CONTENT")]
+    pub log_content: bool,
+}
+
+/// Everything the server binary can be configured with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    pub username: UsernameRules,
+    pub heartbeat: HeartbeatConfig,
+    pub outbound: OutboundConfig,
+    pub shutdown: ShutdownConfig,
+    pub limits: LimitsConfig,
+    pub auth: AuthConfig,
+    pub tls: TlsConfig,
+    pub metrics: MetricsConfig,
+    pub admin: AdminConfig,
+    pub log: LogConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1:3000".to_string(),
+            username: UsernameRules::default(),
+            heartbeat: HeartbeatConfig::default(),
+            outbound: OutboundConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            limits: LimitsConfig::default(),
+            auth: AuthConfig::default(),
+            tls: TlsConfig::default(),
+            metrics: MetricsConfig::default(),
+            admin: AdminConfig::default(),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// When set, every handshake must carry a token signed with this secret.
+    pub hmac_secret: Option<String>,
+}
+
+/// Certificate and key for serving `wss://`. TLS is off unless both are set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+}
+
+/// Prometheus endpoint, served on its own address so it can stay private.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub bind: Option<String>,
+}
+
+/// Operator API for inspecting and managing live state.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    pub bind: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// A level or `tracing` filter directives. `RUST_LOG` takes precedence.
+    pub level: String,
+    pub format: LogFormat,
+    /// Log chat message content instead of redacting it.
+    pub log_content: bool,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+            log_content: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl Config {
+    /// Build the effective config: defaults, then the file, then environment
+    /// and flags. Fails if the result is invalid.
+    pub fn load(args: &Args) -> anyhow::Result<Self> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let raw = fs::read_to_string(path)
+                    .with_context(|| format!("failed to read config file {}", path.display()))?;
+                toml::from_str(&raw).with_context(|| format!("invalid config file {}", path.display()))?
+            }
+            None => Config::default(),
+        };
+
+        config.apply(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply(&mut self, args: &Args) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut self.bind, &args.bind);
+        if let Some(secs) = args.heartbeat_interval_secs {
+            self.heartbeat.interval = Duration::from_secs(secs);
+        }
+        if let Some(secs) = args.heartbeat_timeout_secs {
+            self.heartbeat.timeout = Duration::from_secs(secs);
+        }
+        set(&mut self.limits.max_message_size, &args.max_message_size);
+        set(&mut self.limits.max_rooms, &args.max_rooms);
+        set(&mut self.limits.max_room_members, &args.max_room_members);
+        set(&mut self.limits.max_room_name_len, &args.max_room_name_len);
+        set(&mut self.limits.max_room_topic_len, &args.max_room_topic_len);
+        set(&mut self.limits.max_protocol_violations, &args.max_protocol_violations);
+        set(&mut self.username.min_len, &args.username_min_len);
+        set(&mut self.username.max_len, &args.username_max_len);
+        set(&mut self.outbound.capacity, &args.outbound_capacity);
+        set(&mut self.outbound.policy, &args.slow_consumer_policy);
+        if let Some(secs) = args.drain_timeout_secs {
+            self.shutdown.drain_timeout = Duration::from_secs(secs);
+        }
+        if args.hmac_secret.is_some() {
+            self.auth.hmac_secret = args.hmac_secret.clone();
+        }
+        if args.metrics_bind.is_some() {
+            self.metrics.bind = args.metrics_bind.clone();
+        }
+        if args.admin_bind.is_some() {
+            self.admin.bind = args.admin_bind.clone();
+        }
+        if args.admin_token.is_some() {
+            self.admin.token = args.admin_token.clone();
+        }
+        if args.tls_cert.is_some() {
+            self.tls.cert_path = args.tls_cert.clone();
+        }
+        if args.tls_key.is_some() {
+            self.tls.key_path = args.tls_key.clone();
+        }
+        set(&mut self.log.level, &args.log_level);
+        set(&mut self.log.format, &args.log_format);
+        if args.log_content {
+            self.log.log_content = true;
+        }
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.bind.trim().is_empty() {
+            bail!("bind must not be empty");
+        }
+        if self.auth.hmac_secret.as_ref().is_some_and(|s| s.is_empty()) {
+            bail!("auth.hmac_secret must not be empty");
+        }
+        if self.admin.bind.is_some() && self.admin.token.as_ref().is_none_or(|t| t.is_empty()) {
+            bail!("admin.bind requires a non-empty admin.token");
+        }
+        if self.tls.cert_path.is_some() != self.tls.key_path.is_some() {
+            bail!("tls.cert_path and tls.key_path must be set together");
+        }
+        if let Err(e) = EnvFilter::try_new(&self.log.level) {
+            bail!("invalid log.level '{}': {}", self.log.level, e);
+        }
+        self.server_config()
+            .validate()
+            .map_err(|e| anyhow::anyhow!("invalid configuration: {}", e))
+    }
+
+    /// The subset of settings understood by `rws_core::Server`.
+    pub fn server_config(&self) -> ServerConfig {
+        ServerConfig {
+            username: self.username.clone(),
+            heartbeat: self.heartbeat.clone(),
+            outbound: self.outbound.clone(),
+            shutdown: self.shutdown.clone(),
+            limits: self.limits.clone(),
+            logging: LoggingConfig {
+                log_content: self.log.log_content,
+            },
+        }
+    }
+
+    /// TOML rendering of the config with secrets masked.
+    pub fn to_redacted_toml(&self) -> anyhow::Result<String> {
+        let mut shown = self.clone();
+        if shown.auth.hmac_secret.is_some() {
+            shown.auth.hmac_secret = Some("<redacted>".to_string());
+        }
+        if shown.admin.token.is_some() {
+            shown.admin.token = Some("<redacted>".to_string());
+        }
+        Ok(toml::to_string_pretty(&shown)?)
+    }
+}
+
+impl LogConfig {
+    /// Install the global `tracing` subscriber.
+    pub fn init(&self) {
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&self.level));
+        let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+        match self.format {
+            LogFormat::Text => subscriber.init(),
+            LogFormat::Json => subscriber.json().flatten_event(true).with_span_list(false).init(),
+        }
+    }
+}
+
+fn parse_policy(value: &str) -> Result<SlowConsumerPolicy, String> {
+    match value {
+        "drop_oldest" => Ok(SlowConsumerPolicy::DropOldest),
+        "drop_newest" => Ok(SlowConsumerPolicy::DropNewest),
+        "disconnect" => Ok(SlowConsumerPolicy::Disconnect),
+        other => Err(format!("unknown policy '{}', expected drop_oldest, drop_newest or disconnect", other)),
+    }
+}
+
+fn parse_log_format(value: &str) -> Result<LogFormat, String> {
+    match value {
+        "text" => Ok(LogFormat::Text),
+        "json" => Ok(LogFormat::Json),
+        other => Err(format!("unknown log format '{}', expected text or json", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("rws-server").chain(flags.iter().copied())).unwrap()
+    }
+
+    /// Write `contents` to a config file unique to `name`.
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rws-config-{}-{}.toml", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn validation_error(config: Config) -> String {
+        config.validate().unwrap_err().to_string()
+    }
+
+    // Clap settles flags against `RWS_*` variables into one `Args` value, so
+    // this covers both layers without touching the process environment, which
+    // the parallel tests' argument parsing reads.
+    #[test]
+    fn arguments_override_the_file() {
+        let path = config_file("precedence", "[limits]\nmax_rooms = 10\nmax_room_members = 20\nmax_room_name_len = 30\n");
+        let mut args = args(&["--config", path.to_str().unwrap(), "--max-room-name-len", "32"]);
+        // As if set through RWS_MAX_ROOM_MEMBERS
+        args.max_room_members = Some(21);
+        let config = Config::load(&args);
+        let _ = fs::remove_file(&path);
+
+        let limits = config.unwrap().limits;
+        assert_eq!((limits.max_rooms, limits.max_room_members, limits.max_room_name_len), (10, 21, 32));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let path = config_file("unknown", "[limits]\nmax_romos = 10\n");
+        let error = Config::load(&args(&["--config", path.to_str().unwrap()])).unwrap_err();
+        let _ = fs::remove_file(&path);
+        assert!(format!("{:#}", error).contains("unknown field `max_romos`"), "{:#}", error);
+    }
+
+    #[test]
+    fn inconsistent_settings_fail_validation() {
+        let admin = Config { admin: AdminConfig { bind: Some("127.0.0.1:0".into()), token: None }, ..Config::default() };
+        assert!(validation_error(admin).contains("admin.token"));
+
+        let tls = Config { tls: TlsConfig { cert_path: Some("cert.pem".into()), key_path: None }, ..Config::default() };
+        assert!(validation_error(tls).contains("tls.cert_path and tls.key_path"));
+
+        let log = Config { log: LogConfig { level: "info,=[".into(), ..LogConfig::default() }, ..Config::default() };
+        assert!(validation_error(log).contains("invalid log.level"));
+
+        let mut heartbeat = Config::default();
+        heartbeat.apply(&args(&["--heartbeat-interval-secs", "30", "--heartbeat-timeout-secs", "30"]));
+        assert!(validation_error(heartbeat).contains("heartbeat.timeout_secs"));
+
+        Config::default().validate().unwrap();
+    }
+
+    #[test]
+    fn printed_config_redacts_secrets() {
+        let mut config = Config::default();
+        config.apply(&args(&["--hmac-secret", "hmac-value", "--admin-bind", "127.0.0.1:0", "--admin-token", "admin-value"]));
+        let shown = config.to_redacted_toml().unwrap();
+        assert!(!shown.contains("hmac-value") && !shown.contains("admin-value"), "{}", shown);
+        assert_eq!(shown.matches("<redacted>").count(), 2);
+        assert_eq!(config.admin.token.as_deref(), Some("admin-value"));
+    }
+}
@@ -1,7 +1,79 @@
-use rws_server::Server;
+mod config;
+
+use anyhow::Context;
+use clap::Parser;
+use rws_core::{tls, HmacAuthenticator, Server};
+
+use crate::config::{Args, Config};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let server = Server::bind("127.0.0.1:3000").await?;
-    server.run().await
+    let args = Args::parse();
+    let config = Config::load(&args)?;
+
+    if args.print_config {
+        print!("{}", config.to_redacted_toml()?);
+        return Ok(());
+    }
+
+    config.log.init();
+
+    let mut server = bind(&config.bind)
+        .await
+        .with_context(|| format!("failed to bind {}", config.bind))?
+        .with_config(config.server_config());
+    if let Some(secret) = &config.auth.hmac_secret {
+        server = server.with_authenticator(HmacAuthenticator::new(secret.as_bytes()));
+    }
+    if let Some(addr) = &config.metrics.bind {
+        server = server
+            .with_metrics_endpoint(addr.as_str())
+            .await
+            .with_context(|| format!("failed to bind metrics endpoint {}", addr))?;
+    }
+    if let (Some(addr), Some(token)) = (&config.admin.bind, &config.admin.token) {
+        server = server
+            .with_admin_endpoint(addr.as_str(), token.as_str())
+            .await
+            .with_context(|| format!("failed to bind admin API {}", addr))?;
+    }
+    if let (Some(cert), Some(key)) = (&config.tls.cert_path, &config.tls.key_path) {
+        server = server.with_tls(tls::load_acceptor(cert, key)?);
+    }
+
+    server.run_until(shutdown_signal()).await
+}
+
+/// `unix:<path>` binds a Unix domain socket; anything else is a TCP address.
+async fn bind(addr: &str) -> anyhow::Result<Server> {
+    #[cfg(unix)]
+    if let Some(path) = addr.strip_prefix("unix:") {
+        return Server::bind_unix(path);
+    }
+    Server::bind(addr).await
+}
+
+/// Resolves on Ctrl+C, or SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }